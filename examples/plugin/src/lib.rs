@@ -0,0 +1,66 @@
+//! A Dingle plugin adding vector arithmetic. Build it with `cargo build --release -p dingle-example-plugin`,
+//! then `load_plugin("target/release/libdingle_example_plugin.so")` or pass it with `--plugin`.
+
+use dinglebob::plugin::{ABI_VERSION, PluginFn, PluginRegistrar, PluginValue};
+use std::cell::RefCell;
+
+thread_local! {
+    /// Backs the list `scale` returns until the host has copied it
+    static RESULT: RefCell<Vec<PluginValue>> = const { RefCell::new(Vec::new()) };
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn dingle_plugin_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// # Safety
+/// Called by the interpreter with a valid registrar, once, after the version check
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dingle_plugin_register(registrar: *mut PluginRegistrar) {
+    let registrar = unsafe { &*registrar };
+    let define = |name: &str, arity: u32, func: PluginFn| unsafe {
+        (registrar.define)(registrar.context, name.as_ptr(), name.len(), arity, func)
+    };
+
+    define("dot", 2, dot);
+    define("scale", 2, scale);
+}
+
+/// Reads a List of numbers
+unsafe fn floats(value: &PluginValue) -> Option<Vec<f64>> {
+    unsafe { value.as_list() }?.iter().map(PluginValue::as_float).collect()
+}
+
+unsafe fn fail(out: *mut PluginValue, message: &'static str) -> i32 {
+    unsafe { *out = PluginValue::string(message) };
+    1
+}
+
+/// `dot(xs, ys)`: the dot product of two equally long Lists of numbers
+unsafe extern "C" fn dot(args: *const PluginValue, _argc: usize, out: *mut PluginValue) -> i32 {
+    let args = unsafe { std::slice::from_raw_parts(args, 2) };
+    let (Some(xs), Some(ys)) = (unsafe { floats(&args[0]) }, unsafe { floats(&args[1]) }) else {
+        return unsafe { fail(out, "Type error: 'dot' expects two Lists of numbers.") };
+    };
+    if xs.len() != ys.len() {
+        return unsafe { fail(out, "Value error: 'dot' expects Lists of the same length.") };
+    }
+
+    unsafe { *out = PluginValue::float(xs.iter().zip(&ys).map(|(x, y)| x * y).sum()) };
+    0
+}
+
+/// `scale(xs, k)`: a new List with every number in `xs` multiplied by `k`
+unsafe extern "C" fn scale(args: *const PluginValue, _argc: usize, out: *mut PluginValue) -> i32 {
+    let args = unsafe { std::slice::from_raw_parts(args, 2) };
+    let (Some(xs), Some(k)) = (unsafe { floats(&args[0]) }, args[1].as_float()) else {
+        return unsafe { fail(out, "Type error: 'scale' expects a List of numbers and a number.") };
+    };
+
+    RESULT.with_borrow_mut(|result| {
+        *result = xs.iter().map(|x| PluginValue::float(x * k)).collect();
+        unsafe { *out = PluginValue::list(result) };
+    });
+    0
+}
@@ -1,9 +1,8 @@
-use crate::interpreter;
 use crate::token::Token;
-use crate::token::TokenKind;
-use crate::interpreter::Interpreter;
 use crate::environment::Environment;
 use crate::func::Func;
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::host::HostObject;
 use std::fmt::{Debug, Formatter, Result};
 use std::rc::Rc;
 use std::cell::RefCell; 
@@ -18,10 +17,18 @@ pub enum Expression {
     Logical(Box<Expression>, Token, Box<Expression>),
     Literal(Value), 
     Grouping(Box<Expression>),
-    Variable(Token),
+    Variable(Token, Option<Local>), // filled in by the resolver for locals; None means a global
     List(Box<Vec<Expression>>, Token),
-    Lambda(Vec<Token>, Box<Vec<Statement>>), 
-    Index(Box<Expression>, Token,  Box<Expression>)
+    Lambda(Vec<Token>, Rc<[Statement]>), // bodies are shared with every closure made from them
+    Index(Box<Expression>, Token,  Box<Expression>),
+    Get(Box<Expression>, Token)
+}
+
+/// Where the resolver found a local: how many frames up, and its slot in that frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Local {
+    pub depth: usize,
+    pub slot: usize,
 }
 
 #[derive(Clone)]
@@ -34,6 +41,8 @@ pub enum Value
     Bool(bool),
     Call(Rc<dyn Func>, Rc<RefCell<Environment>>),
     List(Rc<RefCell<Vec<Value>>>),
+    Module(String, Rc<RefCell<Environment>>),
+    Native(Rc<dyn HostObject>), // an embedder's object; see `host`
     None
 }
 
@@ -44,8 +53,10 @@ impl Debug for Value {
             Value::Int(i) => f.debug_tuple("Int").field(i).finish(),
             Value::Float(fl) => f.debug_tuple("Float").field(fl).finish(),
             Value::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
-            Value::Call(callee, env) => write!(f, "{}", format!("Call(<{}>)", callee.toString())),
+            Value::Call(callee, _env) => write!(f, "Call(<{}>)", callee.toString()),
             Value::List(lst) => f.debug_tuple("List").field(lst).finish(),
+            Value::Module(name, _) => write!(f, "Module(<{}>)", name),
+            Value::Native(object) => write!(f, "{:?}", object),
             Value::None => write!(f, "None"),
         }
     }
@@ -56,19 +67,40 @@ impl Debug for Value {
 pub enum Statement {
     Var(Token, Expression),
     Expression(Expression), 
-    Function(Token, Vec<Token>, Box<Vec<Statement>>),
+    Function(Token, Vec<Token>, Rc<[Statement]>),
     If(Expression, Box<Statement>, Box<Statement>), //For the case of no else, just set to some useless expression. 
     Print(Expression),
     Return(Token, Expression),
     While(Expression, Box<Statement>),
     Break(Token), 
-    Block(Box<Vec<Statement>>)
+    Block(Box<Vec<Statement>>),
+    Export(Token, Box<Statement>),
+    FromImport(Token, Vec<(Token, Token)>) // (path string, [(exported name, local alias)])
 }
 
 #[derive(Debug, Clone)]
 
 pub enum BreakResult {
     Return(Token, Value), 
-    Error(String), 
+    Error(Box<Diagnostic>), 
     Break(Token), 
+    Exit(i32), 
+}
+
+impl BreakResult {
+    /// A runtime error with no place in the source yet; the call it escapes from reports it
+    pub fn error(message: impl Into<String>) -> Self {
+        BreakResult::Error(Box::new(Diagnostic::new(Phase::Runtime, message)))
+    }
+
+    /// The same, with an error code; without one, the call reports it as a built-in's error
+    pub fn error_code(code: &str, message: impl Into<String>) -> Self {
+        BreakResult::Error(Box::new(Diagnostic::new(Phase::Runtime, message).with_code(code)))
+    }
+}
+
+impl From<Diagnostic> for BreakResult {
+    fn from(diagnostic: Diagnostic) -> Self {
+        BreakResult::Error(Box::new(diagnostic))
+    }
 }
\ No newline at end of file
@@ -1,25 +1,29 @@
 // src/bin/uitest.rs
 use std::{
+    env,
     ffi::OsStr,
     fs,
     io::{self, Write},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     process::Command,
 };
 
+use dinglebob::{explain, Engine, OutputBuffer};
+
 fn main() -> io::Result<()> {
+    // The plugin tests load the example plugin, which is a crate of its own
     let status = Command::new("cargo")
-        .args(["build", "--release"])
+        .args(["build", "--release", "-p", "dingle-example-plugin"])
         .status()?;
 
     if !status.success() {
         std::process::exit(1);
     }
 
-    let exe = PathBuf::from("target/release/dinglebob");
-    if !exe.exists() {
-        std::process::exit(1);
-    }
+    // Read by the plugin tests with `env`; relative, so the snapshots don't depend on the checkout.
+    // SAFETY: nothing else is running yet, and the runner never starts a thread.
+    unsafe { env::set_var("DINGLE_EXAMPLE_PLUGIN", example_plugin()) };
 
     let test_dir = Path::new("uitest");
     if !test_dir.exists() {
@@ -36,39 +40,217 @@ fn main() -> io::Result<()> {
     let mut any_failed = false;
 
     for test_path in tests {
-        let output = Command::new(&exe)
-            // Try to disable colors at the source (best-effort)
-            .env("NO_COLOR", "1")
-            .env("CLICOLOR", "0")
-            .env("CLICOLOR_FORCE", "0")
-            .env("TERM", "dumb")
-            .arg(&test_path)
-            .output()?;
-
-        let stderr_path = PathBuf::from(format!("{}.stderr", test_path.display()));
+        let mut jitted = Vec::new();
+        let script_args = header(&test_path, "# args:")?;
+        let run = capture(|engine| {
+            engine.set_script_args(script_args);
+            let status = run_file(engine, &test_path.display().to_string());
+            jitted = jit_outcomes(engine);
+            status
+        });
+        any_failed |= snapshot(&test_path, &run)?;
 
-        let stdout_clean = strip_ansi_escapes::strip(&output.stdout);
-        let stderr_clean = strip_ansi_escapes::strip(&output.stderr);
-
-        let stderr_path = PathBuf::from(format!("{}.stderr", test_path.display()));
+        if cfg!(feature = "jit") {
+            any_failed |= check_jit(&test_path, &jitted)?;
+        }
+    }
 
-        let stdout_clean = strip_ansi_escapes::strip(&output.stdout);
-        let stderr_clean = strip_ansi_escapes::strip(&output.stderr);
+    // Each directory in uitest/projects is run like `dinglebob run` from inside it
+    let projects_dir = test_dir.join("projects");
+    let mut projects: Vec<PathBuf> = match fs::read_dir(&projects_dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.join("dingle.toml").is_file())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
 
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&stdout_clean);
-        bytes.extend_from_slice(&stderr_clean);
+    projects.sort();
 
-        fs::write(&stderr_path, bytes)?;
+    let root = env::current_dir()?;
+    for project_path in projects {
+        env::set_current_dir(&project_path)?;
+        let run = capture(run_project);
+        env::set_current_dir(&root)?;
 
-        if !output.status.success() {
-            any_failed = true;
-        }
+        any_failed |= snapshot(&project_path, &run)?;
     }
 
+    any_failed |= check_explanations(Path::new("src"))?;
+
     if any_failed {
         std::process::exit(1);
     }
 
     Ok(())
 }
+
+/// Checks that every error code the sources attach to a diagnostic has an entry for `--explain`,
+/// and that every entry is still used; returns true if one isn't
+fn check_explanations(src: &Path) -> io::Result<bool> {
+    let mut used = Vec::new();
+    collect_codes(src, &mut used)?;
+
+    let mut failed = false;
+    for (code, file) in &used {
+        if explain::explanation(code).is_none() {
+            eprintln!("{}: error code {} has no explanation in explain/", file.display(), code);
+            failed = true;
+        }
+    }
+    for code in explain::codes() {
+        if !used.iter().any(|(c, _)| c == code) {
+            eprintln!("explain/{}.md: no diagnostic uses this code", code);
+            failed = true;
+        }
+    }
+
+    Ok(failed)
+}
+
+/// Every `"D<4 digits>"` string literal in the Rust files under `dir`, but for the explanations' own table
+fn collect_codes(dir: &Path, used: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_codes(&path, used)?;
+            continue;
+        }
+        if path.extension() != Some(OsStr::new("rs")) || path.ends_with("explain.rs") {
+            continue;
+        }
+
+        let text = fs::read_to_string(&path)?;
+        for (i, _) in text.match_indices("\"D") {
+            let code = &text[i + 1..];
+            let digits = code[1..].chars().take_while(|c| c.is_ascii_digit()).count();
+            if digits == 4 && code[5..].starts_with('"') {
+                used.push((String::from(&code[..5]), path.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What one test printed, and how it ended
+struct Run {
+    stdout: String,
+    stderr: String,
+    // None if the interpreter panicked
+    status: Option<i32>,
+}
+
+/// Runs a test on a reporting engine whose output goes to buffers, like the `dinglebob` command's would
+fn capture(test: impl FnOnce(&mut Engine) -> i32) -> Run {
+    let stdout = OutputBuffer::new();
+    let stderr = OutputBuffer::new();
+
+    let mut engine = Engine::reporting();
+    engine.set_stdout(stdout.clone());
+    engine.set_stderr(stderr.clone());
+    // Gives the import tests a search path directory
+    engine.add_search_path(PathBuf::from("uitest/lib"));
+
+    let status = panic::catch_unwind(AssertUnwindSafe(|| test(&mut engine))).ok();
+
+    Run { stdout: stdout.contents(), stderr: stderr.contents(), status }
+}
+
+/// `dinglebob <path>`
+fn run_file(engine: &mut Engine, path: &str) -> i32 {
+    // The engine writes the reports to the stderr buffer itself
+    match engine.run_file(path) {
+        Ok(code) => code,
+        Err(diagnostic) => diagnostic.exit_status(),
+    }
+}
+
+/// `dinglebob run`, from the project's directory
+fn run_project(engine: &mut Engine) -> i32 {
+    match engine.load_project() {
+        Ok(entry) => run_file(engine, &entry),
+        Err(diagnostic) => diagnostic.exit_status(),
+    }
+}
+
+/// Where `cargo build --release` puts examples/plugin on this platform
+fn example_plugin() -> String {
+    format!(
+        "target/release/{}dingle_example_plugin{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    )
+}
+
+/// Which of "native", "bailout" and "fallback" happened to at least one call to a hot function
+#[cfg(feature = "jit")]
+fn jit_outcomes(engine: &Engine) -> Vec<&'static str> {
+    let stats = engine.jit_stats();
+    [("native", stats.native), ("bailout", stats.bailouts), ("fallback", stats.fallbacks)]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(outcome, _)| outcome)
+        .collect()
+}
+
+#[cfg(not(feature = "jit"))]
+fn jit_outcomes(_engine: &Engine) -> Vec<&'static str> {
+    Vec::new()
+}
+
+/// The words after `prefix` on a test's first line, if it starts with it, like `# args: a b`
+fn header(test_path: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let source = fs::read_to_string(test_path)?;
+    let words = match source.lines().next().and_then(|line| line.strip_prefix(prefix)) {
+        Some(rest) => rest.split_whitespace().map(String::from).collect(),
+        None => Vec::new(),
+    };
+    Ok(words)
+}
+
+/// Checks a test's `# jit: <outcome>...` first line, if it has one, against what the JIT did;
+/// returns true if an outcome it names never happened
+fn check_jit(test_path: &Path, jitted: &[&str]) -> io::Result<bool> {
+    let mut failed = false;
+    for outcome in header(test_path, "# jit:")? {
+        if !jitted.contains(&outcome.as_str()) {
+            eprintln!("{}: expected a JIT {}, but none happened", test_path.display(), outcome);
+            failed = true;
+        }
+    }
+    Ok(failed)
+}
+
+/// Writes `<path>.stderr`; returns true if the run crashed rather than failing normally. With the
+/// JIT on, the snapshot must instead match the interpreter's one already there, and a difference
+/// is a failure too.
+fn snapshot(test_path: &Path, run: &Run) -> io::Result<bool> {
+    let stderr_path = PathBuf::from(format!("{}.stderr", test_path.display()));
+
+    let stdout_clean = strip_ansi_escapes::strip(&run.stdout);
+    let stderr_clean = strip_ansi_escapes::strip(&run.stderr);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&stdout_clean);
+    bytes.extend_from_slice(&stderr_clean);
+
+    // Failing programs must exit non-zero, so the status is part of the snapshot
+    if let Some(code) = run.status.filter(|c| *c != 0) {
+        bytes.extend_from_slice(format!("[exit status: {}]\n", code).as_bytes());
+    }
+
+    if cfg!(feature = "jit") {
+        let expected = fs::read(&stderr_path).unwrap_or_default();
+        if bytes != expected {
+            eprintln!("{}: output with the JIT differs from the interpreter's", stderr_path.display());
+            let _ = io::stderr().write_all(&bytes);
+            return Ok(true);
+        }
+        return Ok(run.status.is_none());
+    }
+
+    fs::write(&stderr_path, bytes)?;
+
+    // Error tests exit 1 on purpose; only a panic is a failure of the harness
+    Ok(run.status.is_none())
+}
@@ -0,0 +1,487 @@
+use crate::ast::{Expression, Statement, Value};
+use crate::token::{Token, TokenKind};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// One bytecode instruction. Operands index into the owning `Proto`'s tables.
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Constant(u32),
+    Pop,
+    GetLocal(u32),
+    // Pops into the slot, through the captured cell if a closure shares it
+    SetLocal(u32),
+    // Pops into the slot as a fresh binding, so earlier closures keep the old one
+    DefineLocal(u32),
+    ClearLocal(u32),
+    GetUpvalue(u32),
+    SetUpvalue(u32),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    DefineGlobal(u32),
+    Binary(TokenKind, u32),
+    Unary(u32),
+    Jump(u32),
+    JumpIfFalse(u32),
+    // Short-circuit jumps: keep the operand if jumping, pop it otherwise
+    JumpIfFalseKeep(u32),
+    JumpIfTrueKeep(u32),
+    // (argument count, paren token, where a `break` escaping the callee lands)
+    Call(u32, u32, Option<u32>),
+    Closure(u32),
+    Return,
+    // `return` / `break` outside any function or loop: unwinds to the host, like the tree-walker
+    ReturnOut(u32),
+    BreakOut(u32),
+    Print,
+    List(u32),
+    Index(u32),
+    SetIndex(u32),
+    SetMember(u32),
+    Get(u32),
+    FromImport(u32),
+}
+
+/// Where a closure's upvalue comes from: a slot of the enclosing frame, or one of its upvalues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capture {
+    pub local: bool,
+    pub index: u32,
+}
+
+/// A compiled function body (or the top-level script)
+pub struct Proto {
+    // None for lambdas and the script
+    pub name: Option<Token>,
+    pub params: Vec<Token>,
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    // Span table: every token an instruction may report an error at
+    pub tokens: Vec<Token>,
+    pub protos: Vec<Rc<Proto>>,
+    pub captures: Vec<Capture>,
+    pub slots: usize,
+    pub imports: Vec<(Token, Vec<(Token, Token)>)>,
+    // The source body, which the JIT profiles and compiles; None for the script
+    #[cfg(feature = "jit")]
+    pub body: Option<Rc<[Statement]>>,
+}
+
+/// Why a program wasn't compiled; it runs on the tree-walking interpreter instead. The reason
+/// only shows when debugging with `{:?}`.
+#[derive(Debug)]
+pub struct Unsupported(#[allow(dead_code)] pub String);
+
+type CompileResult<T> = Result<T, Unsupported>;
+
+struct Scope {
+    declared: HashMap<String, u32>,
+    // Every name this scope declares, including ones not reached yet
+    later: HashSet<String>,
+}
+
+struct FnState {
+    proto: Proto,
+    scopes: Vec<Scope>,
+    next_slot: u32,
+    // Per enclosing loop: the jumps and calls to point at its exit
+    loops: Vec<Vec<usize>>,
+}
+
+enum Resolved {
+    Local(u32),
+    Upvalue(u32),
+    Global,
+}
+
+pub fn compile(statements: &[Statement]) -> CompileResult<Rc<Proto>> {
+    let mut compiler = Compiler { fns: vec![FnState::new(None, Vec::new())] };
+
+    for stmt in statements {
+        compiler.statement(stmt)?;
+    }
+
+    let none = compiler.constant(Value::None);
+    compiler.emit(Op::Constant(none));
+    compiler.emit(Op::Return);
+
+    let script = compiler.fns.pop().unwrap();
+    Ok(Rc::new(script.proto))
+}
+
+impl FnState {
+    fn new(name: Option<Token>, params: Vec<Token>) -> Self {
+        FnState {
+            proto: Proto {
+                name,
+                params,
+                code: Vec::new(),
+                constants: Vec::new(),
+                tokens: Vec::new(),
+                protos: Vec::new(),
+                captures: Vec::new(),
+                slots: 0,
+                imports: Vec::new(),
+                #[cfg(feature = "jit")]
+                body: None,
+            },
+            scopes: Vec::new(),
+            next_slot: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// Names a block declares directly, for telling "not declared yet" apart from "not declared here"
+fn declared_names(statements: &[Statement]) -> HashSet<String> {
+    statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Var(name, _) | Statement::Function(name, _, _) => Some(name.lexeme.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Compiler {
+    fns: Vec<FnState>,
+}
+
+impl Compiler {
+    fn current(&mut self) -> &mut FnState {
+        self.fns.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let code = &mut self.current().proto.code;
+        code.push(op);
+        code.len() - 1
+    }
+
+    fn here(&mut self) -> u32 {
+        self.current().proto.code.len() as u32
+    }
+
+    fn constant(&mut self, value: Value) -> u32 {
+        let constants = &mut self.current().proto.constants;
+        constants.push(value);
+        (constants.len() - 1) as u32
+    }
+
+    fn token(&mut self, token: &Token) -> u32 {
+        let tokens = &mut self.current().proto.tokens;
+        tokens.push(token.clone());
+        (tokens.len() - 1) as u32
+    }
+
+    fn patch(&mut self, at: usize, target: u32) {
+        let code = &mut self.current().proto.code;
+        code[at] = match code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpIfFalseKeep(_) => Op::JumpIfFalseKeep(target),
+            Op::JumpIfTrueKeep(_) => Op::JumpIfTrueKeep(target),
+            Op::Call(argc, paren, _) => Op::Call(argc, paren, Some(target)),
+            other => other,
+        };
+    }
+
+    /// True at the top level of the script, where declarations are globals
+    fn at_global_scope(&self) -> bool {
+        self.fns.len() == 1 && self.fns[0].scopes.is_empty()
+    }
+
+    fn begin_scope(&mut self, later: HashSet<String>) {
+        self.current().scopes.push(Scope { declared: HashMap::new(), later });
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.current();
+        let scope = state.scopes.pop().unwrap();
+        state.next_slot -= scope.declared.len() as u32;
+    }
+
+    /// The resolver has already rejected a name declared twice in one scope
+    fn declare_local(&mut self, name: &Token) -> u32 {
+        let state = self.current();
+        let slot = state.next_slot;
+        let scope = state.scopes.last_mut().unwrap();
+
+        scope.declared.insert(name.lexeme.clone(), slot);
+        state.next_slot += 1;
+        state.proto.slots = state.proto.slots.max(state.next_slot as usize);
+        slot
+    }
+
+    /// Resolves a name lexically. The tree-walker lets a closure reach a local declared after the
+    /// closure was created, once that declaration has run; those programs aren't compiled.
+    fn resolve(&mut self, name: &Token) -> CompileResult<Resolved> {
+        let top = self.fns.len() - 1;
+
+        for fi in (0..=top).rev() {
+            for scope in self.fns[fi].scopes.iter().rev() {
+                if let Some(&slot) = scope.declared.get(&name.lexeme) {
+                    if fi == top {
+                        return Ok(Resolved::Local(slot));
+                    }
+                    return Ok(Resolved::Upvalue(self.capture(fi, slot)));
+                }
+
+                if fi != top && scope.later.contains(&name.lexeme) {
+                    return Err(Unsupported(format!("'{}' is captured before it is declared", name.lexeme)));
+                }
+            }
+        }
+
+        Ok(Resolved::Global)
+    }
+
+    /// Threads a slot of function `owner` down to the innermost function as an upvalue
+    fn capture(&mut self, owner: usize, slot: u32) -> u32 {
+        let mut capture = Capture { local: true, index: slot };
+
+        for fi in owner + 1..self.fns.len() {
+            let captures = &mut self.fns[fi].proto.captures;
+            let index = match captures.iter().position(|c| *c == capture) {
+                Some(i) => i,
+                None => {
+                    captures.push(capture);
+                    captures.len() - 1
+                }
+            };
+            capture = Capture { local: false, index: index as u32 };
+        }
+
+        capture.index
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> CompileResult<()> {
+        match stmt {
+            Statement::Expression(exp) => {
+                self.expression(exp)?;
+                self.emit(Op::Pop);
+            }
+            Statement::If(exp, then_s, else_s) => {
+                self.expression(exp)?;
+                let to_else = self.emit(Op::JumpIfFalse(0));
+                self.statement(then_s)?;
+                let to_end = self.emit(Op::Jump(0));
+                let else_at = self.here();
+                self.patch(to_else, else_at);
+                self.statement(else_s)?;
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Statement::Function(name, params, body) => {
+                if self.at_global_scope() {
+                    self.function(Some(name), params, body)?;
+                    let t = self.token(name);
+                    self.emit(Op::DefineGlobal(t));
+                } else {
+                    // Declared first so the body can call itself
+                    let slot = self.declare_local(name);
+                    self.emit(Op::ClearLocal(slot));
+                    self.function(Some(name), params, body)?;
+                    self.emit(Op::SetLocal(slot));
+                }
+            }
+            Statement::Print(exp) => {
+                self.expression(exp)?;
+                self.emit(Op::Print);
+            }
+            Statement::Return(t, val) => {
+                self.expression(val)?;
+                if self.fns.len() == 1 {
+                    let t = self.token(t);
+                    self.emit(Op::ReturnOut(t));
+                } else {
+                    self.emit(Op::Return);
+                }
+            }
+            Statement::Var(var, value) => {
+                // The initializer can't see the variable it initializes
+                self.expression(value)?;
+                if self.at_global_scope() {
+                    let t = self.token(var);
+                    self.emit(Op::DefineGlobal(t));
+                } else {
+                    let slot = self.declare_local(var);
+                    self.emit(Op::DefineLocal(slot));
+                }
+            }
+            Statement::Block(statements) => {
+                self.begin_scope(declared_names(statements));
+                for stmt in statements.iter() {
+                    self.statement(stmt)?;
+                }
+                self.end_scope();
+            }
+            Statement::While(exp, body) => {
+                let start = self.here();
+                self.expression(exp)?;
+                let to_exit = self.emit(Op::JumpIfFalse(0));
+
+                self.current().loops.push(vec![to_exit]);
+                self.statement(body)?;
+                self.emit(Op::Jump(start));
+
+                let exit = self.here();
+                let exits = self.current().loops.pop().unwrap();
+                for at in exits {
+                    self.patch(at, exit);
+                }
+            }
+            Statement::Break(t) => {
+                if self.current().loops.is_empty() {
+                    let t = self.token(t);
+                    self.emit(Op::BreakOut(t));
+                } else {
+                    let at = self.emit(Op::Jump(0));
+                    self.current().loops.last_mut().unwrap().push(at);
+                }
+            }
+            Statement::Export(_, stmt) => self.statement(stmt)?,
+            Statement::FromImport(path, names) => {
+                let imports = &mut self.current().proto.imports;
+                imports.push((path.clone(), names.clone()));
+                let index = (imports.len() - 1) as u32;
+                self.emit(Op::FromImport(index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles a function body into a child proto and emits the closure that captures its upvalues
+    fn function(&mut self, name: Option<&Token>, params: &[Token], body: &Rc<[Statement]>) -> CompileResult<()> {
+        self.fns.push(FnState::new(name.cloned(), params.to_vec()));
+        #[cfg(feature = "jit")]
+        {
+            self.current().proto.body = Some(Rc::clone(body));
+        }
+
+        // Parameters and the body are separate scopes, as in `Function::call`
+        let param_names = params.iter().map(|p| p.lexeme.clone()).collect();
+        self.begin_scope(param_names);
+        for param in params {
+            self.declare_local(param);
+        }
+
+        self.begin_scope(declared_names(body));
+        for stmt in body.iter() {
+            self.statement(stmt)?;
+        }
+        self.end_scope();
+        self.end_scope();
+
+        let none = self.constant(Value::None);
+        self.emit(Op::Constant(none));
+        self.emit(Op::Return);
+
+        let child = self.fns.pop().unwrap();
+        let protos = &mut self.current().proto.protos;
+        protos.push(Rc::new(child.proto));
+        let index = (protos.len() - 1) as u32;
+        self.emit(Op::Closure(index));
+        Ok(())
+    }
+
+    fn expression(&mut self, exp: &Expression) -> CompileResult<()> {
+        match exp {
+            Expression::Literal(v) => {
+                let c = self.constant(v.clone());
+                self.emit(Op::Constant(c));
+            }
+            Expression::Grouping(exp) => self.expression(exp)?,
+            Expression::Variable(t, _) => {
+                let op = match self.resolve(t)? {
+                    Resolved::Local(slot) => Op::GetLocal(slot),
+                    Resolved::Upvalue(index) => Op::GetUpvalue(index),
+                    Resolved::Global => Op::GetGlobal(self.token(t)),
+                };
+                self.emit(op);
+            }
+            Expression::Assign(target, _eq, value) => {
+                // Same order as the tree-walker: the value first, then the target's parts
+                self.expression(value)?;
+                match target.as_ref() {
+                    Expression::Variable(t, _) => {
+                        let op = match self.resolve(t)? {
+                            Resolved::Local(slot) => Op::SetLocal(slot),
+                            Resolved::Upvalue(index) => Op::SetUpvalue(index),
+                            Resolved::Global => Op::SetGlobal(self.token(t)),
+                        };
+                        self.emit(op);
+                        let none = self.constant(Value::None);
+                        self.emit(Op::Constant(none));
+                    }
+                    Expression::Index(list, t, index) => {
+                        self.expression(list)?;
+                        self.expression(index)?;
+                        let t = self.token(t);
+                        self.emit(Op::SetIndex(t));
+                    }
+                    Expression::Get(object, name) => {
+                        self.expression(object)?;
+                        let t = self.token(name);
+                        self.emit(Op::SetMember(t));
+                    }
+                    _ => return Err(Unsupported(String::from("invalid assignment target"))),
+                }
+            }
+            Expression::Binary(l, o, r) => {
+                self.expression(l)?;
+                self.expression(r)?;
+                let t = self.token(o);
+                self.emit(Op::Binary(o.kind, t));
+            }
+            Expression::Unary(o, r) => {
+                self.expression(r)?;
+                let t = self.token(o);
+                self.emit(Op::Unary(t));
+            }
+            Expression::Logical(l, o, r) => {
+                self.expression(l)?;
+                let skip = if o.kind == TokenKind::OR {
+                    self.emit(Op::JumpIfTrueKeep(0))
+                } else {
+                    self.emit(Op::JumpIfFalseKeep(0))
+                };
+                self.expression(r)?;
+                let end = self.here();
+                self.patch(skip, end);
+            }
+            Expression::Call(callee, paren, args) => {
+                self.expression(callee)?;
+                for arg in args.iter() {
+                    self.expression(arg)?;
+                }
+                let t = self.token(paren);
+                let at = self.emit(Op::Call(args.len() as u32, t, None));
+
+                // A `break` escaping the callee ends the loop around the call
+                if let Some(exits) = self.current().loops.last_mut() {
+                    exits.push(at);
+                }
+            }
+            Expression::Lambda(params, body) => self.function(None, params, body)?,
+            Expression::Index(list, t, index) => {
+                self.expression(list)?;
+                self.expression(index)?;
+                let t = self.token(t);
+                self.emit(Op::Index(t));
+            }
+            Expression::List(items, _t) => {
+                for item in items.iter() {
+                    self.expression(item)?;
+                }
+                self.emit(Op::List(items.len() as u32));
+            }
+            Expression::Get(object, name) => {
+                self.expression(object)?;
+                let t = self.token(name);
+                self.emit(Op::Get(t));
+            }
+        }
+        Ok(())
+    }
+}
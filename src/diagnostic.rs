@@ -0,0 +1,407 @@
+use crate::source::{SourceId, SourceMap};
+use crate::token::Token;
+use ariadne::{ColorGenerator, Report, ReportKind};
+use std::fmt;
+use std::io::{self, Write};
+
+/// How many errors scanning or parsing one file reports before giving up, unless configured otherwise
+pub const MAX_ERRORS: usize = 20;
+
+/// The stage of running a program an error came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Reading a source file
+    Io,
+    Scan,
+    Parse,
+    Resolve,
+    Runtime,
+    /// Loading or creating a project: its manifest, dependencies and lockfile
+    Project,
+}
+
+impl Phase {
+    /// Lowercase, as JSON output names it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Io => "io",
+            Phase::Scan => "scan",
+            Phase::Parse => "parse",
+            Phase::Resolve => "resolve",
+            Phase::Runtime => "runtime",
+            Phase::Project => "project",
+        }
+    }
+
+    /// The heading reports from this phase carry
+    pub fn title(&self) -> &'static str {
+        match self {
+            Phase::Io => "IO Error",
+            Phase::Scan => "Scanner Error",
+            Phase::Parse => "Parser Error",
+            Phase::Resolve => "Resolver Error",
+            Phase::Runtime => "Interpreter Error",
+            Phase::Project => "Project Error",
+        }
+    }
+}
+
+/// How errors are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Ariadne reports quoting the source
+    #[default]
+    Human,
+    /// One JSON object per diagnostic per line, for editors and CI
+    Json,
+}
+
+/// The version of the JSON output's schema, written into every object; bumped when fields change meaning or go away
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A range of characters in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: String,
+    /// The text in the run's `SourceMap` the offsets are into
+    pub source: SourceId,
+    /// Character offsets into the file, end exclusive
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line of `start`
+    pub line: usize,
+}
+
+impl Span {
+    pub(crate) fn of(token: &Token) -> Self {
+        Span {
+            file: token.file.clone(),
+            source: token.source,
+            start: token.id as usize,
+            end: token.id_end as usize,
+            line: token.line.max(0) as usize,
+        }
+    }
+}
+
+/// One call on the stack when a runtime error was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The function called, as `print` would show it
+    pub function: String,
+    /// Its call's parenthesis
+    pub call_site: Span,
+}
+
+/// How many frames of a trace get a label; deep recursion would bury the error
+const TRACE_LABELS: usize = 16;
+
+/// A secondary span, pointing at something that explains the error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An error or warning from any phase. Nothing prints it where it happens: it's rendered once,
+/// by whoever ends up handling it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    /// A stable identifier for this kind of error, if it has one
+    pub code: Option<String>,
+    pub message: String,
+    /// Where it happened; None for errors with no place in the source, like a missing file
+    pub span: Option<Span>,
+    /// For an error without a span, the file it's about if there is one, like an invalid manifest
+    pub file: Option<String>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    /// The error that led to this one, e.g. the one inside a function whose call failed. Reported first.
+    pub cause: Option<Box<Diagnostic>>,
+    /// Further errors the same phase found after this one, each reported after it
+    pub more: Vec<Diagnostic>,
+    /// For a runtime error, the calls it was raised inside, outermost first
+    pub trace: Vec<Frame>,
+}
+
+impl Diagnostic {
+    pub fn new(phase: Phase, message: impl Into<String>) -> Self {
+        Diagnostic {
+            phase,
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            span: None,
+            file: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            cause: None,
+            more: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// All the errors one phase collected, as one diagnostic led by the first; None if there were none
+    pub(crate) fn collect(mut errors: Vec<Diagnostic>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        let mut first = errors.remove(0);
+        first.more = errors;
+        Some(first)
+    }
+
+    /// How many errors this reports: itself and the ones in `more`
+    pub fn count(&self) -> usize {
+        1 + self.more.len()
+    }
+
+    /// The status a process that failed with this exits with: how many errors it reports, capped
+    /// at 255, as statuses wrap at 256 and 256 errors would read as success
+    ///
+    /// ```
+    /// use dinglebob::{Diagnostic, Phase};
+    ///
+    /// let mut diagnostic = Diagnostic::new(Phase::Parse, "Expected ';' after expression.");
+    /// diagnostic.more = vec![diagnostic.clone(); 255];
+    /// assert_eq!(diagnostic.count(), 256);
+    /// assert_eq!(diagnostic.exit_status(), 255);
+    /// ```
+    pub fn exit_status(&self) -> i32 {
+        self.count().min(255) as i32
+    }
+
+    /// An error at `token`
+    pub(crate) fn at(phase: Phase, message: impl Into<String>, token: &Token) -> Self {
+        Diagnostic::new(phase, message).with_span(Span::of(token))
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_cause(mut self, cause: Diagnostic) -> Self {
+        self.cause = Some(Box::new(cause));
+        self
+    }
+
+    /// Writes the report for this diagnostic to `out`, after the reports for its causes, quoting
+    /// the code its spans point at from `sources`. A cause without a span is only shown through
+    /// the message of the diagnostic that wraps it.
+    pub fn render(&self, sources: &SourceMap, out: &mut dyn Write) -> io::Result<()> {
+        for diagnostic in self.reports() {
+            match &diagnostic.span {
+                Some(span) => diagnostic.report(span, sources, out)?,
+                // Coded like ariadne's reports, so the code can be looked up with --explain
+                None => match &diagnostic.code {
+                    Some(code) => writeln!(out, "[{}] {}", code, diagnostic)?,
+                    None => writeln!(out, "{}", diagnostic)?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the same diagnostics `render` would report to `out` as JSON, one object per line;
+    /// see "Machine-readable errors" in the README for the schema
+    pub fn render_json(&self, sources: &SourceMap, out: &mut dyn Write) -> io::Result<()> {
+        for diagnostic in self.reports() {
+            writeln!(out, "{}", diagnostic.to_json(sources))?;
+        }
+        Ok(())
+    }
+
+    /// Renders as `format` asks
+    pub fn render_as(&self, format: ErrorFormat, sources: &SourceMap, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            ErrorFormat::Human => self.render(sources, out),
+            ErrorFormat::Json => self.render_json(sources, out),
+        }
+    }
+
+    /// This diagnostic and the ones reported with it, in order: its causes that have a place in
+    /// the source, then itself, then the errors in `more`
+    fn reports(&self) -> Vec<&Diagnostic> {
+        let mut list = Vec::new();
+        self.push_causes(&mut list);
+        list.push(self);
+        for diagnostic in &self.more {
+            list.extend(diagnostic.reports());
+        }
+        list
+    }
+
+    fn push_causes<'a>(&'a self, list: &mut Vec<&'a Diagnostic>) {
+        if let Some(cause) = &self.cause {
+            cause.push_causes(list);
+            if cause.span.is_some() {
+                list.push(cause);
+            }
+            for diagnostic in &cause.more {
+                list.extend(diagnostic.reports());
+            }
+        }
+    }
+
+    /// This diagnostic alone, without its causes or `more`, as one line of JSON
+    fn to_json(&self, sources: &SourceMap) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|label| format!("{{\"message\":{},{}}}", json_string(&label.message), span_json(&label.span, sources)))
+            .collect();
+        let trace: Vec<String> = self
+            .trace
+            .iter()
+            .map(|frame| format!("{{\"function\":{},{}}}", json_string(&frame.function), span_json(&frame.call_site, sources)))
+            .collect();
+        let notes: Vec<String> = self.notes.iter().map(|note| json_string(note)).collect();
+
+        format!(
+            "{{\"version\":{},\"phase\":\"{}\",\"severity\":\"{}\",\"code\":{},\"message\":{},{},\"labels\":[{}],\"trace\":[{}],\"notes\":[{}],\"help\":{}}}",
+            JSON_VERSION,
+            self.phase.name(),
+            severity,
+            self.code.as_deref().map_or(String::from("null"), json_string),
+            json_string(&self.message),
+            match &self.span {
+                Some(span) => span_json(span, sources),
+                None => format!("\"file\":{},\"range\":null", self.file.as_deref().map_or(String::from("null"), json_string)),
+            },
+            labels.join(","),
+            trace.join(","),
+            notes.join(","),
+            self.help.as_deref().map_or(String::from("null"), json_string),
+        )
+    }
+
+    fn report(&self, span: &Span, sources: &SourceMap, out: &mut dyn Write) -> io::Result<()> {
+        let mut colors = ColorGenerator::new();
+        let kind = match self.severity {
+            Severity::Error => ReportKind::Error,
+            Severity::Warning => ReportKind::Warning,
+        };
+
+        let mut report = Report::build(kind, (span.source, span.line.saturating_sub(1)..3))
+            .with_message(self.phase.title())
+            .with_label(
+                ariadne::Label::new((span.source, span.start..span.end))
+                    .with_message(&self.message)
+                    .with_color(colors.next()),
+            );
+
+        for label in &self.labels {
+            report = report.with_label(
+                ariadne::Label::new((label.span.source, label.span.start..label.span.end))
+                    .with_message(&label.message)
+                    .with_color(colors.next()),
+            );
+        }
+        // Numbered from the outermost call, as ariadne orders labels by position
+        let shown = self.trace.len().saturating_sub(TRACE_LABELS);
+        for (i, frame) in self.trace.iter().enumerate().skip(shown) {
+            report = report.with_label(
+                ariadne::Label::new((frame.call_site.source, frame.call_site.start..frame.call_site.end))
+                    .with_message(format!("{}. in a call to '{}'", i + 1, frame.function))
+                    .with_color(colors.next()),
+            );
+        }
+        if shown > 0 {
+            report = report.with_note(format!("{} outer call(s) not shown.", shown));
+        }
+
+        if let Some(code) = &self.code {
+            report = report.with_code(code);
+        }
+        for note in &self.notes {
+            report = report.with_note(note);
+        }
+        if let Some(help) = &self.help {
+            report = report.with_help(help);
+        }
+
+        report.finish().write(sources, out)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// One line: the phase's heading and the message
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.phase {
+            // Already phrased as a whole sentence, e.g. "IO error: could not read ..."
+            Phase::Io => write!(f, "{}", self.message),
+            phase => write!(f, "{}: {}", phase.title(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// `"file":...,"range":...` for a span, with 1-based lines and columns counted in characters
+fn span_json(span: &Span, sources: &SourceMap) -> String {
+    let (start_line, start_column) = sources.position(span.source, span.start);
+    let (end_line, end_column) = sources.position(span.source, span.end);
+    format!(
+        "\"file\":{},\"range\":{{\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}}}",
+        json_string(sources.name(span.source)),
+        start_line, start_column, end_line, end_column
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,374 @@
+use crate::ast::{self, Statement};
+use crate::diagnostic::{Diagnostic, ErrorFormat, Phase};
+use crate::func::Func;
+use crate::interpreter::Interpreter;
+#[cfg(feature = "jit")]
+use crate::jit::JitStats;
+use crate::native::{Native, NativeFn};
+use crate::parser::Parser;
+use crate::plugin;
+use crate::project;
+use crate::resolver::Resolver;
+use crate::scanner::scan;
+use crate::source::SourceMap;
+use crate::value::Value;
+use crate::vm;
+use std::cell::Ref;
+use std::env;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// A Dingle interpreter to embed: globals persist from one `eval` or `run_file` to the next
+pub struct Engine {
+    resolver: Resolver,
+    interpreter: Interpreter,
+    // The code the program passed to `exit`, once it has
+    exit: Option<i32>,
+}
+
+impl Engine {
+    /// An engine that prints nothing of its own: errors only come back as diagnostics
+    pub fn new() -> Self {
+        Engine::with_reports(false)
+    }
+
+    /// An engine that also prints a report to stderr for every error, as the command line does
+    pub fn reporting() -> Self {
+        Engine::with_reports(true)
+    }
+
+    fn with_reports(reports: bool) -> Self {
+        // A quiet interpreter keeps the messages of errors inside calls whole, as no report shows them
+        let interpreter = Interpreter::new(!reports);
+        Engine {
+            resolver: Resolver::new(),
+            interpreter,
+            exit: None,
+        }
+    }
+
+    /// Sends what the program prints to `out` instead of the process's stdout. An `OutputBuffer`
+    /// captures it in memory, and any `BufRead` can stand in for stdin:
+    ///
+    /// ```
+    /// use dinglebob::{Engine, OutputBuffer};
+    /// use std::io::Cursor;
+    ///
+    /// let out = OutputBuffer::new();
+    /// let mut engine = Engine::new();
+    /// engine.set_stdout(out.clone());
+    /// engine.set_stdin(Cursor::new("Ada\n"));
+    ///
+    /// engine.eval("print \"Hello, \" + input(\"Name? \");").unwrap();
+    /// assert_eq!(out.contents(), "Name? Hello, Ada\n");
+    /// ```
+    pub fn set_stdout(&mut self, out: impl Write + 'static) {
+        *self.interpreter.streams.stdout.borrow_mut() = Box::new(out);
+    }
+
+    /// Sends error reports to `err` instead of the process's stderr
+    pub fn set_stderr(&mut self, err: impl Write + 'static) {
+        *self.interpreter.streams.stderr.borrow_mut() = Box::new(err);
+    }
+
+    /// Makes `input` and `read_line` read from `input` instead of the process's stdin
+    pub fn set_stdin(&mut self, input: impl BufRead + 'static) {
+        *self.interpreter.streams.stdin.borrow_mut() = Box::new(input);
+    }
+
+    /// Makes the `random` module replay the same sequence on every run
+    pub fn seed(&mut self, seed: u64) {
+        self.interpreter.rng.borrow_mut().seed(seed);
+    }
+
+    /// What the script sees as its command-line arguments
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.interpreter.script_args = Rc::new(args);
+    }
+
+    /// Runs programs on the tree-walking interpreter instead of the bytecode VM
+    pub fn set_tree_walker(&mut self, tree_walker: bool) {
+        self.interpreter.tree_walker = tree_walker;
+    }
+
+    /// How many calls to hot functions ran as native code, bailed out of it, or fell back to the
+    /// interpreter so far
+    #[cfg(feature = "jit")]
+    pub fn jit_stats(&self) -> JitStats {
+        self.interpreter.jit.borrow().stats
+    }
+
+    /// Writes the reports of a reporting engine as JSON, one object per diagnostic and line,
+    /// instead of quoting the source:
+    ///
+    /// ```
+    /// use dinglebob::{Engine, ErrorFormat, OutputBuffer};
+    ///
+    /// let err = OutputBuffer::new();
+    /// let mut engine = Engine::reporting();
+    /// engine.set_stderr(err.clone());
+    /// engine.set_error_format(ErrorFormat::Json);
+    ///
+    /// engine.eval("let x = 1 / 0;").unwrap_err();
+    /// assert_eq!(
+    ///     err.contents(),
+    ///     concat!(
+    ///         r#"{"version":1,"phase":"runtime","severity":"error","code":"D0402","#,
+    ///         r#""message":"Division by zero.","file":"<eval>","#,
+    ///         r#""range":{"start":{"line":1,"column":11},"end":{"line":1,"column":12}},"#,
+    ///         r#""labels":[],"trace":[],"notes":[],"help":null}"#,
+    ///         "\n"
+    ///     )
+    /// );
+    /// ```
+    pub fn set_error_format(&mut self, format: ErrorFormat) {
+        self.interpreter.error_format = format;
+    }
+
+    /// Stops scanning or parsing a file after `max` errors (at least 1); the default is 20. Every
+    /// error found comes back together, the first leading and the rest in its `more`:
+    ///
+    /// ```
+    /// use dinglebob::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let errors = engine.eval("let = 1; print 2 +; let x = (3;").unwrap_err();
+    /// assert_eq!(errors.count(), 3);
+    ///
+    /// engine.set_max_errors(2);
+    /// assert_eq!(engine.eval("let = 1; print 2 +; let x = (3;").unwrap_err().count(), 2);
+    /// ```
+    pub fn set_max_errors(&mut self, max: usize) {
+        self.interpreter.max_errors = max.max(1);
+    }
+
+    /// Also looks for imports in `dir`, after the importing file's directory and before the standard library
+    pub fn add_search_path(&mut self, dir: PathBuf) {
+        self.interpreter.imports.borrow_mut().search_path.push(dir);
+    }
+
+    /// Makes `import("name")` load the package whose entry file is `entry`
+    pub fn add_package(&mut self, name: &str, entry: PathBuf) {
+        self.interpreter.imports.borrow_mut().packages.insert(String::from(name), entry);
+    }
+
+    /// Finds the project around the working directory as `dinglebob run` does, makes its packages
+    /// importable, and returns the path of its entry point to pass to `run_file`
+    pub fn load_project(&mut self) -> Result<String, Diagnostic> {
+        let loaded = project::find_from_cwd().and_then(|manifest| project::load(&manifest));
+        let project = self.report(loaded)?;
+
+        for (name, entry) in project.packages {
+            self.add_package(&name, entry);
+        }
+
+        // Shorter paths in error reports when running from inside the project
+        Ok(match env::current_dir() {
+            Ok(cwd) => project::display(&project.entry, &cwd),
+            Err(_) => project.entry.display().to_string(),
+        })
+    }
+
+    /// Prints the report for an error the host ran into itself, as a reporting engine does for its own
+    pub fn report_error(&self, diagnostic: &Diagnostic) {
+        self.interpreter.render(diagnostic);
+    }
+
+    /// The code the program passed to `exit`, if it has called it
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit
+    }
+
+    /// Runs a file as the main program; returns the code it passed to `exit`, or 0
+    pub fn run_file(&mut self, path: &str) -> Result<i32, Diagnostic> {
+        let result = self.run_main(path);
+        self.report(result)
+    }
+
+    fn run_main(&mut self, path: &str) -> Result<i32, Diagnostic> {
+        let source = fs::read_to_string(path)
+            .map_err(|_| Diagnostic::new(Phase::Io, format!("Could not read file '{}'", path)).with_code("D0001"))?;
+
+        self.interpreter.imports.borrow_mut().enter_main(path);
+        let statements = self.compile(&source, String::from(path))?;
+
+        let code = vm::run_program(&mut self.interpreter, &statements)?;
+        self.exit = code;
+        Ok(code.unwrap_or(0))
+    }
+
+    /// Runs a snippet of code, returning the value of its last statement if that's an expression.
+    /// If it fails, the engine's globals are left as they were before, as functions defined
+    /// earlier see them too:
+    ///
+    /// ```
+    /// use dinglebob::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.eval("let x = 1; define f() { return x; }").unwrap();
+    /// engine.eval("x = 5; let y = 1 / 0;").unwrap_err();
+    /// assert_eq!(engine.call("f", vec![]), Ok(Value::Int(1)));
+    ///
+    /// engine.eval("x = 7;").unwrap();
+    /// assert_eq!(engine.get_global("x"), Some(Value::Int(7)));
+    /// assert_eq!(engine.call("f", vec![]), Ok(Value::Int(7)));
+    /// ```
+    pub fn eval(&mut self, source: &str) -> Result<Value, Diagnostic> {
+        let resolver_save = self.resolver.clone();
+        // Restored into the same environment, which the functions defined so far close over
+        let globals = Rc::clone(&self.interpreter.global_environment);
+        let bindings_save = globals.borrow().hashMap.clone();
+
+        let result = self.eval_statements(source);
+        if let Err(diagnostic) = &result {
+            self.resolver = resolver_save;
+            // Only running the code could have changed the globals
+            if diagnostic.phase == Phase::Runtime {
+                globals.borrow_mut().hashMap = bindings_save;
+            }
+        }
+        self.report(result)
+    }
+
+    fn eval_statements(&mut self, source: &str) -> Result<Value, Diagnostic> {
+        let statements = self.compile(source, String::from("<eval>"))?;
+
+        let (body, last) = match statements.split_last() {
+            Some((Statement::Expression(exp), body)) => (body, Some(exp)),
+            _ => (&statements[..], None),
+        };
+
+        if let Some(code) = vm::run_program(&mut self.interpreter, body)? {
+            self.exit = Some(code);
+            return Ok(Value::None);
+        }
+
+        let Some(exp) = last else {
+            return Ok(Value::None);
+        };
+
+        match self.interpreter.evaluate(exp) {
+            Ok(value) => Ok(Value::from_internal(&value)),
+            Err(result) => self.finish(result),
+        }
+    }
+
+    /// Calls a function the program defined (or a built-in) by name. An error has no place in the
+    /// source unless it was raised inside the program's code, but has a code all the same:
+    ///
+    /// ```
+    /// use dinglebob::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let error = engine.call("len", vec![Value::Int(1)]).unwrap_err();
+    /// assert_eq!(error.code.as_deref(), Some("D0420"));
+    /// assert_eq!(engine.call("len", vec![]).unwrap_err().code.as_deref(), Some("D0405"));
+    /// ```
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Diagnostic> {
+        let result = self.call_global(name, args);
+        self.report(result)
+    }
+
+    fn call_global(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Diagnostic> {
+        let Some(callee) = self.interpreter.global_environment.borrow().lookup(name) else {
+            return Err(Diagnostic::new(
+                Phase::Runtime,
+                format!("Undefined variable '{}': no binding found in this scope (or any enclosing scope).", name),
+            )
+            .with_code("D0101"));
+        };
+
+        let args = args.into_iter().map(Value::into_internal).collect();
+        match self.interpreter.call_value(&callee, args) {
+            Ok(value) => Ok(Value::from_internal(&value)),
+            Err(result) => self.finish(result),
+        }
+    }
+
+    /// Adds a Rust closure as a built-in, visible to the program and every module it imports.
+    /// Arguments are converted from the parameter types, and checked for count and type first:
+    ///
+    /// ```
+    /// use dinglebob::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.register_fn("shout", |s: String, times: usize| -> Result<String, String> {
+    ///     Ok(s.to_uppercase().repeat(times))
+    /// });
+    /// assert_eq!(engine.eval("shout(\"hi\", 2);"), Ok(Value::String(String::from("HIHI"))));
+    ///
+    /// let error = engine.eval("shout(\"hi\", -1);").unwrap_err();
+    /// assert!(error.message.ends_with("Value error: argument 2 to 'shout' is out of range for a non-negative Int, got -1."));
+    /// ```
+    ///
+    /// Closures are `Fn`, so host state they change goes in a `Cell` or `RefCell`.
+    pub fn register_fn<Args>(&mut self, name: &str, f: impl NativeFn<Args>) {
+        let func: Rc<dyn Func> = Rc::new(Native::new(name, f));
+        self.interpreter.natives.borrow_mut().push((String::from(name), Rc::clone(&func)));
+        self.interpreter.define_builtin(name, func);
+    }
+
+    /// Opens a plugin, a shared library built against the `plugin` ABI, and adds its functions
+    /// as built-ins like `register_fn` does. A missing file or symbol, or an ABI version other
+    /// than `plugin::ABI_VERSION`, is a runtime error.
+    pub fn load_plugin(&mut self, path: &str) -> Result<(), Diagnostic> {
+        let result = plugin::load(&self.interpreter, path)
+            .map(|_| ())
+            .map_err(|msg| Diagnostic::new(Phase::Runtime, msg).with_code("D0412"));
+        self.report(result)
+    }
+
+    /// The text of every file, module and snippet run so far, for `Diagnostic::render`
+    pub fn sources(&self) -> Ref<'_, SourceMap> {
+        self.interpreter.sources.borrow()
+    }
+
+    /// Defines a global, or replaces the one already bound to `name`
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.global_environment.borrow_mut().define_default(name, value.into_internal());
+    }
+
+    /// A global's current value; built-ins included
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let value = self.interpreter.global_environment.borrow().lookup(name)?;
+        Some(Value::from_internal(&value))
+    }
+
+    /// Scans, parses and resolves `source`; `file` is the name errors are reported under
+    fn compile(&mut self, source: &str, file: String) -> Result<Vec<Statement>, Diagnostic> {
+        let id = self.interpreter.sources.borrow_mut().add(&file, source);
+        let max_errors = self.interpreter.max_errors;
+        let tokens = scan(source, false, file, id, max_errors)?;
+        let mut statements = Parser::new(tokens, max_errors).parse()?;
+        self.resolver.resolve(&mut statements)?;
+
+        Ok(statements)
+    }
+
+    /// How a call or expression that didn't produce a value ended: `exit`, or an error
+    fn finish(&mut self, result: ast::BreakResult) -> Result<Value, Diagnostic> {
+        match self.interpreter.finish(Err(result)) {
+            Ok(code) => {
+                self.exit = code;
+                Ok(Value::None)
+            }
+            Err(diagnostic) => Err(diagnostic),
+        }
+    }
+
+    /// Prints the report for an error to stderr, on a reporting engine
+    fn report<T>(&self, result: Result<T, Diagnostic>) -> Result<T, Diagnostic> {
+        if let Err(diagnostic) = &result {
+            self.interpreter.render(diagnostic);
+        }
+        result
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
@@ -4,57 +4,64 @@ use crate::ast::BreakResult;
 use crate::token::Token;
 use std::rc::Rc;
 use std::cell::RefCell;
-use ariadne::{ColorGenerator, Label, Report, ReportKind, Source};
-use std::fs;
+use crate::diagnostic::{Diagnostic, Phase};
 
 pub struct Environment {
     env_superior: Option<Rc<RefCell<Environment>>>,
+    // Named bindings: globals, built-ins and module members
     pub hashMap: HashMap<String, Value>,
-    repl: bool
+    // Locals of a block or call frame, in the order the resolver numbered them
+    slots: Vec<Value>,
+    is_frame: bool,
 }
 
 impl Environment {
-    pub fn new(env_superior: Option<Rc<RefCell<Environment>>>, repl: bool) -> Self {
+    pub fn new(env_superior: Option<Rc<RefCell<Environment>>>) -> Self {
         Environment {
             env_superior,
             hashMap: HashMap::new(),
-            repl: repl
+            slots: Vec::new(),
+            is_frame: false,
         }
     }
 
-    pub fn clone(&self) -> Self {
-        let mut dummy_env_superior = None;
-
-        if !self.env_superior.is_none() {
-            dummy_env_superior = Some(Rc::new(RefCell::new(
-                self.env_superior.clone().unwrap().borrow().clone(),
-            )));
-        }
-
+    /// A block or call frame: its variables are slots, only reachable through resolved references
+    pub fn frame(env_superior: Rc<RefCell<Environment>>) -> Self {
         Environment {
-            env_superior: dummy_env_superior,
-            hashMap: self.hashMap.clone(),
-            repl: self.repl
+            is_frame: true,
+            ..Environment::new(Some(env_superior))
         }
     }
 
     pub fn define(&mut self, var: Token, value: Value) -> Result<Value, BreakResult> {
+        // The resolver already rejected duplicates and numbered this as the next slot
+        if self.is_frame {
+            self.slots.push(value);
+            return Ok(Value::None);
+        }
+
         if self.hashMap.contains_key(&var.lexeme) {
-            return Err(BreakResult::Error(self.handle_error(
+            return Err(self.handle_error(
+                "D0102",
                 &format!("Name '{}' is already defined in this scope.", &var.lexeme),
                 &var,
-            )));
+            ));
         }
 
         self.hashMap.insert(var.lexeme.clone(), value);
         Ok(Value::None)
     }
 
+    /// Appends the next slot of a frame, e.g. a call's arguments
+    pub fn push(&mut self, value: Value) {
+        self.slots.push(value);
+    }
+
     pub fn define_from_execute(&mut self, var: String, value: Value) -> Result<Value, BreakResult> {
         // No token/span info here, so we can't Ariadne-highlight.
         if self.hashMap.contains_key(&var) {
-            return Err(BreakResult::Error(format!(
-                "Interpreter Error: Name '{}' is already defined",
+            return Err(BreakResult::error_code("D0102", format!(
+                "Name '{}' is already defined",
                 &var
             )));
         }
@@ -67,6 +74,14 @@ impl Environment {
         self.hashMap.insert(String::from(name), value);
     }
 
+    /// Defines a name in the outermost environment, the prelude that holds the built-ins
+    pub fn define_builtin(&mut self, name: &str, value: Value) {
+        match &self.env_superior {
+            Some(env) => env.borrow_mut().define_builtin(name, value),
+            None => self.define_default(name, value),
+        }
+    }
+
     fn retrieve(&self, token: &Token) -> Option<Value> {
         if let Some(v) = self.hashMap.get(&token.lexeme) {
             return Some(v.clone());
@@ -78,39 +93,72 @@ impl Environment {
         }
     }
 
+    /// `get` without building an error, for callers that keep the token to report with
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(v) = self.hashMap.get(name) {
+            return Some(v.clone());
+        }
+
+        match &self.env_superior {
+            Some(env) => env.borrow().lookup(name),
+            None => None,
+        }
+    }
+
+    /// `assign` without building an error; false if the name isn't bound anywhere
+    pub fn assign_existing(&mut self, name: &str, value: Value) -> bool {
+        if let Some(slot) = self.hashMap.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+
+        match &self.env_superior {
+            Some(env) => env.borrow_mut().assign_existing(name, value),
+            None => false,
+        }
+    }
+
     pub fn get(&self, token: Token) -> Result<Value, BreakResult> {
         match self.retrieve(&token) {
             Some(v) => Ok(v),
-            None => Err(BreakResult::Error(self.handle_error(
+            None => Err(self.handle_error(
+                "D0101",
                 &format!(
                     "Undefined variable '{}': no binding found in this scope (or any enclosing scope).",
                     token.lexeme
                 ),
                 &token,
-            ))),
+            )),
         }
     }
 
-    pub fn get_at(&self, token: Token, steps: i32) -> Result<Value, BreakResult> {
-        if steps == 0 {
-            return match self.hashMap.get(&token.lexeme) {
-                Some(v) => Ok(v.clone()),
-                None => Err(BreakResult::Error(self.handle_error(
-                    &format!(
-                        "Resolved variable '{}' not found at the expected scope depth (resolver bug or stale locals map).",
-                        &token.lexeme
-                    ),
-                    &token,
-                ))),
+    /// A resolved local; None while its declaration hasn't run yet
+    pub fn get_at(&self, depth: usize, slot: usize) -> Option<Value> {
+        if depth == 0 {
+            return self.slots.get(slot).cloned();
+        }
+
+        match &self.env_superior {
+            Some(env) => env.borrow().get_at(depth - 1, slot),
+            None => None,
+        }
+    }
+
+    /// Sets a resolved local; false while its declaration hasn't run yet
+    pub fn assign_at(&mut self, depth: usize, slot: usize, value: Value) -> bool {
+        if depth == 0 {
+            return match self.slots.get_mut(slot) {
+                Some(v) => {
+                    *v = value;
+                    true
+                }
+                None => false,
             };
         }
 
         match &self.env_superior {
-            Some(env) => env.borrow().get_at(token, steps - 1),
-            None => Err(BreakResult::Error(self.handle_error(
-                &format!("Resolver depth {} exceeds the available environment chain.", steps),
-                &token,
-            ))),
+            Some(env) => env.borrow_mut().assign_at(depth - 1, slot, value),
+            None => false,
         }
     }
 
@@ -121,46 +169,19 @@ impl Environment {
         } else {
             match &mut self.env_superior {
                 Some(env) => env.borrow_mut().assign(token, value),
-                None => Err(BreakResult::Error(self.handle_error(
+                None => Err(self.handle_error(
+                    "D0103",
                     &format!(
                         "Assignment to undefined variable '{}'. Declare it before assigning.",
                         &token.lexeme
                     ),
                     &token,
-                ))),
+                )),
             }
         }
     }
 
-    fn handle_error(&self, msg: &str, token: &Token) -> String {
-
-        let line = token.line;
-        let file = token.file.clone();
-        let start = token.id as usize;
-        let end = token.id_end as usize;
-
-        if !self.repl{ 
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
-
-            let src = fs::read_to_string(&file)
-                .unwrap_or_else(|_| "<could not read source file>".to_string());
-
-            Report::build(
-                ReportKind::Error,
-                (&file, (line.saturating_sub(1)) as usize..3),
-            )
-            .with_message("Interpreter Error")
-            .with_label(
-                Label::new((&file, start..end))
-                    .with_message(msg)
-                    .with_color(a),
-            )
-            .finish()
-            .print((&file, Source::from(&src)))
-            .unwrap();
-        }
-
-        format!("Interpreter Error: {}", msg)
+    fn handle_error(&self, code: &str, msg: &str, token: &Token) -> BreakResult {
+        Diagnostic::at(Phase::Runtime, msg, token).with_code(code).into()
     }
 }
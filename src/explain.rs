@@ -0,0 +1,55 @@
+//! Long-form explanations of error codes, as `dinglebob --explain <code>` prints them
+
+/// Every error code a diagnostic can carry, with its explanation, compiled into the binary
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("D0001", include_str!("../explain/D0001.md")),
+    ("D0002", include_str!("../explain/D0002.md")),
+    ("D0003", include_str!("../explain/D0003.md")),
+    ("D0101", include_str!("../explain/D0101.md")),
+    ("D0102", include_str!("../explain/D0102.md")),
+    ("D0103", include_str!("../explain/D0103.md")),
+    ("D0201", include_str!("../explain/D0201.md")),
+    ("D0202", include_str!("../explain/D0202.md")),
+    ("D0203", include_str!("../explain/D0203.md")),
+    ("D0204", include_str!("../explain/D0204.md")),
+    ("D0205", include_str!("../explain/D0205.md")),
+    ("D0206", include_str!("../explain/D0206.md")),
+    ("D0207", include_str!("../explain/D0207.md")),
+    ("D0208", include_str!("../explain/D0208.md")),
+    ("D0209", include_str!("../explain/D0209.md")),
+    ("D0301", include_str!("../explain/D0301.md")),
+    ("D0302", include_str!("../explain/D0302.md")),
+    ("D0401", include_str!("../explain/D0401.md")),
+    ("D0402", include_str!("../explain/D0402.md")),
+    ("D0403", include_str!("../explain/D0403.md")),
+    ("D0404", include_str!("../explain/D0404.md")),
+    ("D0405", include_str!("../explain/D0405.md")),
+    ("D0406", include_str!("../explain/D0406.md")),
+    ("D0407", include_str!("../explain/D0407.md")),
+    ("D0408", include_str!("../explain/D0408.md")),
+    ("D0409", include_str!("../explain/D0409.md")),
+    ("D0410", include_str!("../explain/D0410.md")),
+    ("D0411", include_str!("../explain/D0411.md")),
+    ("D0412", include_str!("../explain/D0412.md")),
+    ("D0420", include_str!("../explain/D0420.md")),
+    ("D0499", include_str!("../explain/D0499.md")),
+    ("D0501", include_str!("../explain/D0501.md")),
+    ("D0502", include_str!("../explain/D0502.md")),
+    ("D0503", include_str!("../explain/D0503.md")),
+];
+
+/// The explanation of an error code such as `D0201`, with a failing and a fixed example
+///
+/// ```
+/// let text = dinglebob::explain::explanation("D0402").unwrap();
+/// assert!(text.starts_with("A number was divided by zero."));
+/// assert_eq!(dinglebob::explain::explanation("D9999"), None);
+/// ```
+pub fn explanation(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter().find(|(c, _)| *c == code).map(|(_, text)| *text)
+}
+
+/// Every code that has an explanation, in order
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|(code, _)| *code)
+}
@@ -0,0 +1,566 @@
+use crate::interpreter::Interpreter;
+use crate::environment::Environment;
+use crate::ast::Value;
+use crate::ast::BreakResult;
+use crate::func::Func;
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::rc::Rc;
+
+fn io_error(action: &str, path: &str, e: io::Error) -> BreakResult {
+    BreakResult::error(format!("IO error: could not {} '{}': {}.", action, path, e))
+}
+
+/// Reads one line from the program's stdin without its line ending; `none` at end of input
+fn stdin_line(interpreter: &Interpreter) -> Result<Value, BreakResult> {
+    let mut line = String::new();
+
+    match interpreter.streams.stdin.borrow_mut().read_line(&mut line) {
+        Ok(0) => Ok(Value::None),
+        Ok(_) => {
+            let trimmed = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(trimmed);
+            Ok(Value::String(line))
+        }
+        Err(e) => Err(BreakResult::error(format!("IO error: could not read from stdin: {}.", e))),
+    }
+}
+
+pub struct Input;
+
+impl Func for Input {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("input")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() > 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'input' takes 0 or 1 argument (prompt), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        if let Some(prompt) = input_args.first() {
+            let Value::String(prompt) = self.expect(prompt.clone(), "String")? else {unreachable!()};
+            let mut stdout = interpreter.streams.stdout.borrow_mut();
+            write!(stdout, "{}", prompt)
+                .and_then(|_| stdout.flush())
+                .map_err(|e| BreakResult::error(format!("IO error: could not write to stdout: {}.", e)))?;
+        }
+
+        stdin_line(&interpreter)
+    }
+}
+
+pub struct ReadLine;
+
+impl Func for ReadLine {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("read_line")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if !input_args.is_empty() {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'read_line' takes 0 arguments, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        stdin_line(&interpreter)
+    }
+}
+
+pub struct ReadFile;
+
+impl Func for ReadFile {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("read_file")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'read_file' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Value::String(contents)),
+            Err(e) => Err(io_error("read", &path, e)),
+        }
+    }
+}
+
+pub struct ReadLines;
+
+impl Func for ReadLines {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("read_lines")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'read_lines' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let lines = contents.lines().map(|l| Value::String(l.to_string())).collect();
+                Ok(Value::List(Rc::new(RefCell::new(lines))))
+            }
+            Err(e) => Err(io_error("read", &path, e)),
+        }
+    }
+}
+
+/// Shared by `write_file` and `append_file`
+fn write_to(func: &dyn Func, input_args: &[Value], append: bool) -> Result<Value, BreakResult> {
+    if input_args.len() != 2 {
+        return Err(BreakResult::error_code("D0405", format!(
+            "Arity error: '{}' takes 2 arguments (path, string), but got {}.",
+            func.toString(),
+            input_args.len()
+        )));
+    }
+
+    let Value::String(path) = func.expect(input_args[0].clone(), "String")? else {unreachable!()};
+    let Value::String(contents) = func.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+    let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()));
+
+    match result {
+        Ok(()) => Ok(Value::None),
+        Err(e) => Err(io_error("write", &path, e)),
+    }
+}
+
+pub struct WriteFile;
+
+impl Func for WriteFile {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("write_file")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        write_to(self, &input_args, false)
+    }
+}
+
+pub struct AppendFile;
+
+impl Func for AppendFile {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("append_file")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        write_to(self, &input_args, true)
+    }
+}
+
+pub struct FileExists;
+
+impl Func for FileExists {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("file_exists")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'file_exists' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        Ok(Value::Bool(fs::metadata(&path).is_ok()))
+    }
+}
+
+pub struct ListDir;
+
+impl Func for ListDir {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("list_dir")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'list_dir' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        let entries = fs::read_dir(&path).map_err(|e| io_error("list", &path, e))?;
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error("list", &path, e))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+
+        // read_dir order is platform dependent
+        names.sort();
+
+        Ok(Value::List(Rc::new(RefCell::new(names.into_iter().map(Value::String).collect()))))
+    }
+}
+
+pub struct Mkdir;
+
+impl Func for Mkdir {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("mkdir")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'mkdir' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        // Creates missing parents too, and is fine if the directory already exists
+        match fs::create_dir_all(&path) {
+            Ok(()) => Ok(Value::None),
+            Err(e) => Err(io_error("create directory", &path, e)),
+        }
+    }
+}
+
+pub struct Remove;
+
+impl Func for Remove {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("remove")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'remove' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        // Directories must be empty, so a typo can't wipe a whole tree
+        let result = match fs::metadata(&path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir(&path),
+            _ => fs::remove_file(&path),
+        };
+
+        match result {
+            Ok(()) => Ok(Value::None),
+            Err(e) => Err(io_error("remove", &path, e)),
+        }
+    }
+}
+
+enum Stream {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    Closed,
+}
+
+/// An open file; the handle's methods share it
+pub struct FileHandle {
+    path: String,
+    stream: RefCell<Stream>,
+}
+
+impl FileHandle {
+    fn closed_error(&self, method: &str) -> BreakResult {
+        BreakResult::error(format!(
+            "IO error: cannot '{}' file '{}': it is closed.",
+            method, self.path
+        ))
+    }
+
+    fn mode_error(&self, method: &str) -> BreakResult {
+        BreakResult::error(format!(
+            "IO error: cannot '{}' file '{}': it was not opened for that.",
+            method, self.path
+        ))
+    }
+}
+
+pub struct Open;
+
+impl Func for Open {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("open")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'open' takes 2 arguments (path, mode), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(mode) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+        let stream = match mode.as_str() {
+            "r" => File::open(&path).map(|f| Stream::Reader(BufReader::new(f))),
+            "w" => File::create(&path).map(|f| Stream::Writer(BufWriter::new(f))),
+            "a" => OpenOptions::new().append(true).create(true).open(&path).map(|f| Stream::Writer(BufWriter::new(f))),
+            _ => return Err(BreakResult::error(format!(
+                "Value error: 'open' mode must be \"r\", \"w\" or \"a\", got {:?}.",
+                mode
+            ))),
+        }.map_err(|e| io_error("open", &path, e))?;
+
+        let handle = Rc::new(FileHandle { path: path.clone(), stream: RefCell::new(stream) });
+
+        // The handle is a module whose members are bound to this file
+        let members = Rc::new(RefCell::new(Environment::new(None)));
+        {
+            let mut edittable_env = members.borrow_mut();
+
+            let mut define = |name: &str, func: Box<dyn Func>| {
+                edittable_env.define_default(
+                    name,
+                    Value::Call(Rc::from(func), Rc::clone(&interpreter.global_environment))
+                )
+            };
+
+            define("read", Box::new(FileRead { handle: Rc::clone(&handle) }));
+            define("read_line", Box::new(FileReadLine { handle: Rc::clone(&handle) }));
+            define("write", Box::new(FileWrite { handle: Rc::clone(&handle) }));
+            define("close", Box::new(FileClose { handle: Rc::clone(&handle) }));
+
+            edittable_env.define_default("path", Value::String(path.clone()));
+        }
+
+        Ok(Value::Module(format!("file '{}'", path), members))
+    }
+}
+
+pub struct FileRead {
+    handle: Rc<FileHandle>,
+}
+
+impl Func for FileRead {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("read")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if !input_args.is_empty() {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'read' takes 0 arguments, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        // Everything that is left, from wherever the previous reads stopped
+        match &mut *self.handle.stream.borrow_mut() {
+            Stream::Reader(reader) => {
+                let mut rest = String::new();
+                match reader.read_to_string(&mut rest) {
+                    Ok(_) => Ok(Value::String(rest)),
+                    Err(e) => Err(io_error("read", &self.handle.path, e)),
+                }
+            }
+            Stream::Writer(_) => Err(self.handle.mode_error("read")),
+            Stream::Closed => Err(self.handle.closed_error("read")),
+        }
+    }
+}
+
+pub struct FileReadLine {
+    handle: Rc<FileHandle>,
+}
+
+impl Func for FileReadLine {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("read_line")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if !input_args.is_empty() {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'read_line' takes 0 arguments, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        match &mut *self.handle.stream.borrow_mut() {
+            Stream::Reader(reader) => {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) => Ok(Value::None),
+                    Ok(_) => {
+                        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+                        line.truncate(trimmed);
+                        Ok(Value::String(line))
+                    }
+                    Err(e) => Err(io_error("read", &self.handle.path, e)),
+                }
+            }
+            Stream::Writer(_) => Err(self.handle.mode_error("read_line")),
+            Stream::Closed => Err(self.handle.closed_error("read_line")),
+        }
+    }
+}
+
+pub struct FileWrite {
+    handle: Rc<FileHandle>,
+}
+
+impl Func for FileWrite {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("write")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'write' takes 1 argument (string), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(contents) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        match &mut *self.handle.stream.borrow_mut() {
+            Stream::Writer(writer) => match writer.write_all(contents.as_bytes()) {
+                Ok(()) => Ok(Value::None),
+                Err(e) => Err(io_error("write", &self.handle.path, e)),
+            },
+            Stream::Reader(_) => Err(self.handle.mode_error("write")),
+            Stream::Closed => Err(self.handle.closed_error("write")),
+        }
+    }
+}
+
+pub struct FileClose {
+    handle: Rc<FileHandle>,
+}
+
+impl Func for FileClose {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("close")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if !input_args.is_empty() {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'close' takes 0 arguments, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let mut stream = self.handle.stream.borrow_mut();
+
+        // Flush explicitly so write errors surface here instead of being lost on drop
+        if let Stream::Writer(writer) = &mut *stream
+            && let Err(e) = writer.flush() {
+                *stream = Stream::Closed;
+                return Err(io_error("write", &self.handle.path, e));
+            }
+
+        *stream = Stream::Closed;
+        Ok(Value::None)
+    }
+}
@@ -1,17 +1,22 @@
-use crate::interpreter;
 use crate::interpreter::Interpreter;
 use crate::scanner::*;
 use crate::resolver::Resolver; 
 use crate::parser::Parser;
+use crate::stdlib;
+use crate::vm;
+#[cfg(feature = "jit")]
+use crate::jit;
 use crate::ast::Value; 
+use crate::environment::Environment;
 use crate::ast::Statement;
-use crate::ast::Expression;
 use crate::ast::BreakResult;
+use crate::diagnostic::{Diagnostic, Phase};
 use crate::token::Token;
-use std::cell::Ref;
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fs;
-use std::io::LineWriter;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -21,7 +26,7 @@ pub trait Func {
     fn toString(&self) -> String; 
     fn expect(&self, args: Value, value_type: &str) -> Result<Value, BreakResult> {
         let err = |got: Value| {
-            Err(BreakResult::Error(format!(
+            Err(BreakResult::error(format!(
                 "Type error: expected {}, got {:?}.",
                 value_type, got
             )))
@@ -41,9 +46,20 @@ pub trait Func {
             "None" => if matches!(args, Value::None) { Ok(args) } else { err(args) },
             "Call" => if matches!(args, Value::Call(..)) { Ok(args) } else { err(args) },
             "List" => if matches!(args, Value::List(_)) { Ok(args) } else { err(args) },
-            _ => unreachable!(),
+            // Anything else names a host object's type
+            other => match &args {
+                Value::Native(object) if object.type_name() == other => Ok(args),
+                _ => err(args),
+            },
         }
     }
+    fn arity(&self) -> Option<usize> {
+        None
+    }
+    /// The bytecode closure behind this value, so the VM can call it directly
+    fn compiled(&self) -> Option<&crate::vm::Closure> {
+        None
+    }
     fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>; 
 }
 
@@ -60,8 +76,8 @@ impl Func for Timeit {
 
     fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
 
-        if input_args.len() != 0 { 
-            return Err(BreakResult::Error(format!(
+        if !input_args.is_empty() { 
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'timeit' takes 0 arguments, but got {}.",
                 input_args.len()
             )));
@@ -87,9 +103,9 @@ impl Func for Abs {
         return String::from("abs")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
         if input_args.len() != 1 { 
-            return Err(BreakResult::Error(format!(
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'abs' takes 1 argument, but got {}.",
                 input_args.len()
             )));
@@ -113,17 +129,45 @@ impl Func for Len {
         return String::from("len")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
         if input_args.len() != 1 { 
-            return Err(BreakResult::Error(format!(
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'len' takes 1 argument, but got {}.",
                 input_args.len()
             )));
         }
 
-        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        match input_args[0].clone() {
+            Value::String(s) => return Ok(Value::Int(s.chars().count() as i128)),
+            other => {
+                let Value::List(lst) = self.expect(other, "List")? else {unreachable!()};
+                return Ok(Value::Int(lst.borrow().len() as i128));
+            }
+        }
+    }
+}
+
+pub struct Type; 
+
+impl Func for Type { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self ) -> String {
+        return String::from("type")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'type' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
 
-        return Ok(Value::Int(lst.borrow().len() as i128));
+        Ok(Value::String(interpreter.get_type_name(&input_args[0])))
     }
 }
 
@@ -139,9 +183,9 @@ impl Func for Copy {
         return String::from("copy")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
         if input_args.len() != 1 { 
-            return Err(BreakResult::Error(format!(
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'copy' takes 1 argument, but got {}.",
                 input_args.len()
             )));
@@ -165,9 +209,9 @@ impl Func for Append {
         return String::from("append")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
         if input_args.len() != 2 { 
-            return Err(BreakResult::Error(format!(
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'append' takes 2 arguments (list, value), but got {}.",
                 input_args.len()
             )));
@@ -194,9 +238,9 @@ impl Func for Concat {
         return String::from("concat")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
         if input_args.len() != 2 { 
-            return Err(BreakResult::Error(format!(
+            return Err(BreakResult::error_code("D0405", format!(
                 "Arity error: 'concat' takes 2 arguments (list, list), but got {}.",
                 input_args.len()
             )));
@@ -212,186 +256,1640 @@ impl Func for Concat {
     }
 }
 
-pub struct Import; 
+pub struct Split; 
 
-impl Func for Import { 
+impl Func for Split { 
 
     fn isDefault(&self) -> bool {
-        false
+        true
     }
 
     fn toString(&self) -> String {
-        return String::from("import")
+        return String::from("split")
     }
 
-    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'split' takes 2 arguments (string, separator), but got {}.",
+                input_args.len()
+            )));
+        }
 
-        let mut resolver_exe = Resolver::new(false);
-        let mut interpreter_exe = Interpreter::new(true, resolver_exe.give_local(), false);
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(sep) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
 
-        if input_args.len() != 1 { 
-            return Err(BreakResult::Error(format!(
-                "Arity error: 'import' takes 1 arguments String, but got {}.",
+        // An empty separator splits into single characters, like `chars`
+        let parts: Vec<Value> = if sep.is_empty() {
+            s.chars().map(|c| Value::String(c.to_string())).collect()
+        } else {
+            s.split(sep.as_str()).map(|p| Value::String(p.to_string())).collect()
+        };
+
+        return Ok(Value::List(Rc::new(RefCell::new(parts))));
+    }
+}
+
+pub struct Join; 
+
+impl Func for Join { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("join")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'join' takes 2 arguments (list, separator), but got {}.",
                 input_args.len()
             )));
         }
 
-        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let Value::String(sep) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+        let mut parts = Vec::new();
+        for item in lst.borrow().iter() {
+            let Value::String(part) = self.expect(item.clone(), "String")? else {unreachable!()};
+            parts.push(part);
+        }
+
+        return Ok(Value::String(parts.join(&sep)));
+    }
+}
+
+pub struct Trim; 
+
+impl Func for Trim { 
 
-        let contents = fs::read_to_string(&path)
-        .unwrap_or_else(|_| {
-            eprintln!("Could not read file '{}'", path);
-            std::process::exit(1);
-        });
+    fn isDefault(&self) -> bool {
+        true
+    }
 
-        let token_result = scan(&contents, false, path, false);
+    fn toString(&self) -> String {
+        return String::from("trim")
+    }
 
-        if let Err(msg) = token_result{
-             return Err(BreakResult::Error(format!(
-                ""
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'trim' takes 1 argument, but got {}.",
+                input_args.len()
             )));
         }
 
-        let mut parser = Parser::new(token_result.unwrap(), false);
-        let parsed_result: Result<Vec<Statement>, String> = parser.parse();
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        return Ok(Value::String(s.trim().to_string()));
+    }
+}
+
+pub struct Upper; 
+
+impl Func for Upper { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("upper")
+    }
 
-        if let Err(msg) = parsed_result{
-            return Err(BreakResult::Error(format!(
-                ""
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'upper' takes 1 argument, but got {}.",
+                input_args.len()
             )));
         }
 
-        let resolver_result = resolver_exe.resolve((&parsed_result).clone().unwrap());
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        return Ok(Value::String(s.to_uppercase()));
+    }
+}
+
+pub struct Lower; 
+
+impl Func for Lower { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("lower")
+    }
 
-        if let Err(msg) = resolver_result{
-            return Err(BreakResult::Error(format!(
-                ""
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'lower' takes 1 argument, but got {}.",
+                input_args.len()
             )));
         }
 
-        let interpreter_result = interpreter_exe.prime_interpret(parsed_result.unwrap());
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        return Ok(Value::String(s.to_lowercase()));
+    }
+}
+
+pub struct StartsWith; 
+
+impl Func for StartsWith { 
 
-        if let Err(msg) = interpreter_result{
-            return Err(BreakResult::Error(format!(
-                ""
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("starts_with")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'starts_with' takes 2 arguments (string, prefix), but got {}.",
+                input_args.len()
             )));
         }
 
-        for (k, v) in interpreter_exe
-            .global_environment
-            .borrow()
-            .hashMap
-            .clone()
-            .into_iter()
-        {
-            if !k.starts_with('_') {
-                interpreter
-                    .global_environment
-                    .borrow_mut()
-                    .define_from_execute(k, v);
-            }
-        }
-        
-        for (k, v) in interpreter_exe.locals.borrow().clone().into_iter() {
-            interpreter.locals.borrow_mut().insert(k, v);
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(prefix) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+        return Ok(Value::Bool(s.starts_with(prefix.as_str())));
+    }
+}
+
+pub struct EndsWith; 
+
+impl Func for EndsWith { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("ends_with")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'ends_with' takes 2 arguments (string, suffix), but got {}.",
+                input_args.len()
+            )));
         }
 
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(suffix) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
 
-        return Ok(Value::None);
+        return Ok(Value::Bool(s.ends_with(suffix.as_str())));
     }
 }
 
-pub struct Function{
-    pub name: Token, 
-    pub args_list: Vec<Token>, 
-    pub statement_list: Vec<Statement>
+pub struct Find; 
+
+impl Func for Find { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("find")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'find' takes 2 arguments (string, substring), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(sub) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+        // Report a character index so the result can be used with `s[i]`
+        match s.find(sub.as_str()) {
+            Some(byte_index) => return Ok(Value::Int(s[..byte_index].chars().count() as i128)),
+            None => return Ok(Value::Int(-1)),
+        }
+    }
 }
 
-impl Func for Function {
+pub struct Replace; 
+
+impl Func for Replace { 
 
     fn isDefault(&self) -> bool {
-        false
+        true
     }
 
     fn toString(&self) -> String {
-        return self.name.lexeme.clone();
+        return String::from("replace")
     }
 
-    fn call(&self, mut interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>  {
-        if input_args.len() != self.args_list.len() { 
-            return Err(BreakResult::Error(format!(
-                "Arity error: function '{}' expects {} argument(s), but got {}.",
-                self.name.lexeme,
-                self.args_list.len(),
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 3 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'replace' takes 3 arguments (string, from, to), but got {}.",
                 input_args.len()
             )));
         }
 
-        let mut var_list: Vec<Statement> = Vec::new(); 
-        for n in 0..input_args.len(){
-            var_list.push(Statement::Var((&self.args_list[n]).clone(), Expression::Literal(input_args[n].clone())))
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(from) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+        let Value::String(to) = self.expect(input_args[2].clone(), "String")? else {unreachable!()};
+
+        if from.is_empty() {
+            return Err(BreakResult::error("Value error: 'replace' cannot replace an empty string."));
+        }
+
+        return Ok(Value::String(s.replace(from.as_str(), to.as_str())));
+    }
+}
+
+/// The longest string, in bytes, `repeat` and the pad functions build; a larger one would abort
+/// the process when it fails to allocate, rather than raise an error
+const MAX_STRING_BYTES: usize = 1 << 30;
+
+/// The longest list `range` builds, for the same reason
+const MAX_RANGE_LEN: u128 = 1 << 26;
+
+pub struct Repeat; 
+
+impl Func for Repeat { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("repeat")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'repeat' takes 2 arguments (string, count), but got {}.",
+                input_args.len()
+            )));
         }
 
-        var_list.push(Statement::Block(Box::new(self.statement_list.clone())));
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::Int(count) = self.expect(input_args[1].clone(), "Int")? else {unreachable!()};
 
-        match interpreter.interpret(vec![Statement::Block(Box::new(var_list))]) {
-            Ok(_) => {return Ok(Value::None);},
-            Err(BreakResult::Return(_t,v )) => {return Ok(v)},
-            Err(br) => {return Err(br)}
+        if count < 0 {
+            return Err(BreakResult::error(format!(
+                "Value error: 'repeat' count must be non-negative, got {}.",
+                count
+            )));
         }
+
+        let Some(count) = usize::try_from(count).ok().filter(|n| n.saturating_mul(s.len()) <= MAX_STRING_BYTES) else {
+            return Err(BreakResult::error(format!(
+                "Value error: 'repeat' count {} would make a string longer than the limit of {} bytes.",
+                count, MAX_STRING_BYTES
+            )));
+        };
+
+        return Ok(Value::String(s.repeat(count)));
     }
-    
 }
 
-pub struct Lambda{
-    pub args_list: Vec<Token>, 
-    pub statement_list: Vec<Statement>
+pub struct Chars; 
+
+impl Func for Chars { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("chars")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'chars' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        let chars: Vec<Value> = s.chars().map(|c| Value::String(c.to_string())).collect();
+
+        return Ok(Value::List(Rc::new(RefCell::new(chars))));
+    }
 }
 
-impl Func for Lambda {
+pub struct Ord; 
+
+impl Func for Ord { 
 
     fn isDefault(&self) -> bool {
-        false
+        true
     }
 
     fn toString(&self) -> String {
-        let mut text = String::from("Lambda(");
+        return String::from("ord")
+    }
 
-        for i in 0..self.args_list.len(){
-            
-            text += &self.args_list[i].lexeme ; 
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'ord' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
 
-            if i != self.args_list.len() -1 {
-                text += ",";
-            }
+        let Value::String(s) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => return Ok(Value::Int(c as i128)),
+            _ => return Err(BreakResult::error(format!(
+                "Value error: 'ord' expects a single character, got {:?}.",
+                s
+            ))),
         }
+    }
+}
 
-        text += ")";
+pub struct Chr; 
 
-        return text; 
+impl Func for Chr { 
+
+    fn isDefault(&self) -> bool {
+        true
     }
 
-    fn call(&self, mut interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>  {
-        if input_args.len() != self.args_list.len() { 
-            return Err(BreakResult::Error(format!(
-                "Arity error: lambda expects {} argument(s), but got {}.",
-                self.args_list.len(),
+    fn toString(&self) -> String {
+        return String::from("chr")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'chr' takes 1 argument, but got {}.",
                 input_args.len()
             )));
         }
 
-        let mut var_list: Vec<Statement> = Vec::new(); 
-        for n in 0..input_args.len(){
-            var_list.push(Statement::Var((&self.args_list[n]).clone(), Expression::Literal(input_args[n].clone())))
+        let Value::Int(code) = self.expect(input_args[0].clone(), "Int")? else {unreachable!()};
+
+        match u32::try_from(code).ok().and_then(char::from_u32) {
+            Some(c) => return Ok(Value::String(c.to_string())),
+            None => return Err(BreakResult::error(format!(
+                "Value error: 'chr' got {}, which is not a valid Unicode code point.",
+                code
+            ))),
         }
+    }
+}
 
-        var_list.push(Statement::Block(Box::new(self.statement_list.clone())));
+/// Shared by `pad_left` and `pad_right`: (string, width) or (string, width, fill)
+fn pad_args(func: &dyn Func, input_args: &[Value]) -> Result<(String, usize, char), BreakResult> {
+    if input_args.len() != 2 && input_args.len() != 3 { 
+        return Err(BreakResult::error_code("D0405", format!(
+            "Arity error: '{}' takes 2 or 3 arguments (string, width, fill), but got {}.",
+            func.toString(),
+            input_args.len()
+        )));
+    }
 
-        match interpreter.interpret(vec![Statement::Block(Box::new(var_list))]) {
-            Ok(_) => {return Ok(Value::None);},
-            Err(BreakResult::Return(_t,v )) => {return Ok(v)},
-            Err(br) => {return Err(br)}
+    let Value::String(s) = func.expect(input_args[0].clone(), "String")? else {unreachable!()};
+    let Value::Int(width) = func.expect(input_args[1].clone(), "Int")? else {unreachable!()};
+
+    let mut fill = ' ';
+    if input_args.len() == 3 {
+        let Value::String(f) = func.expect(input_args[2].clone(), "String")? else {unreachable!()};
+        let mut f_chars = f.chars();
+        match (f_chars.next(), f_chars.next()) {
+            (Some(c), None) => fill = c,
+            _ => return Err(BreakResult::error(format!(
+                "Value error: '{}' fill must be a single character, got {:?}.",
+                func.toString(), f
+            ))),
         }
     }
+
+    let Some(width) = usize::try_from(width.max(0)).ok().filter(|n| n.saturating_mul(fill.len_utf8()) <= MAX_STRING_BYTES) else {
+        return Err(BreakResult::error(format!(
+            "Value error: '{}' width {} would make a string longer than the limit of {} bytes.",
+            func.toString(), width, MAX_STRING_BYTES
+        )));
+    };
+
+    let missing = width.saturating_sub(s.chars().count());
+    Ok((s, missing, fill))
+}
+
+pub struct PadLeft; 
+
+impl Func for PadLeft { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("pad_left")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        let (s, missing, fill) = pad_args(self, &input_args)?;
+
+        return Ok(Value::String(fill.to_string().repeat(missing) + &s));
+    }
+}
+
+pub struct PadRight; 
+
+impl Func for PadRight { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("pad_right")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        let (s, missing, fill) = pad_args(self, &input_args)?;
+
+        return Ok(Value::String(s + &fill.to_string().repeat(missing)));
+    }
+}
+
+pub struct Format; 
+
+impl Func for Format { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("format")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.is_empty() { 
+            return Err(BreakResult::error_code("D0405", "Arity error: 'format' takes at least 1 argument (template, args...), but got 0."));
+        }
+
+        let Value::String(template) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let values = &input_args[1..];
+
+        // `{}` is a placeholder, `{{` and `}}` are literal braces
+        let mut result = String::new();
+        let mut used = 0;
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) => { chars.next(); result.push('{'); },
+                ('}', Some('}')) => { chars.next(); result.push('}'); },
+                ('{', Some('}')) => {
+                    chars.next();
+                    if used >= values.len() {
+                        return Err(BreakResult::error(format!(
+                            "Format error: template has more '{{}}' placeholders than the {} argument(s) given.",
+                            values.len()
+                        )));
+                    }
+                    result += &Interpreter::stringify(&values[used]);
+                    used += 1;
+                },
+                ('{', _) | ('}', _) => {
+                    return Err(BreakResult::error(format!(
+                        "Format error: unmatched '{}' in template; use '{}{}' for a literal brace.",
+                        c, c, c
+                    )));
+                },
+                _ => result.push(c),
+            }
+        }
+
+        if used != values.len() {
+            return Err(BreakResult::error(format!(
+                "Format error: template has {} placeholder(s), but {} argument(s) were given.",
+                used, values.len()
+            )));
+        }
+
+        return Ok(Value::String(result));
+    }
+}
+
+/// Ordering used by `sort`, `min` and `max`: numbers compare with numbers, strings with strings
+fn compare_values(interpreter: &Interpreter, a: &Value, b: &Value) -> Result<Ordering, BreakResult> {
+    let ordering = match (a, b) {
+        (Value::Int(m), Value::Int(n)) => Some(m.cmp(n)),
+        (Value::Float(m), Value::Float(n)) => m.partial_cmp(n),
+        (Value::Int(m), Value::Float(n)) => (*m as f64).partial_cmp(n),
+        (Value::Float(m), Value::Int(n)) => m.partial_cmp(&(*n as f64)),
+        (Value::String(m), Value::String(n)) => Some(m.cmp(n)),
+        _ => {
+            return Err(BreakResult::error(format!(
+                "Type error: cannot order {} and {}; expected two numbers or two strings.",
+                interpreter.get_type_name(a), interpreter.get_type_name(b)
+            )));
+        }
+    };
+
+    ordering.ok_or_else(|| BreakResult::error("Value error: cannot order NaN."))
+}
+
+/// Stable merge sort with a fallible comparison, so errors raised by a callback stop the sort
+fn merge_sort<T: Clone>(items: Vec<T>, cmp: &mut dyn FnMut(&T, &T) -> Result<Ordering, BreakResult>) -> Result<Vec<T>, BreakResult> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let left = merge_sort(left, cmp)?;
+    let right = merge_sort(right, cmp)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+
+    while i < left.len() && j < right.len() {
+        // Taking from the left on ties keeps equal elements in their original order
+        if cmp(&left[i], &right[j])? != Ordering::Greater {
+            merged.push(left[i].clone());
+            i += 1;
+        } else {
+            merged.push(right[j].clone());
+            j += 1;
+        }
+    }
+
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    Ok(merged)
+}
+
+/// Checks an Int index against a list length; `allow_end` also accepts `len` (for `insert`)
+fn list_index(func: &dyn Func, index: Value, len: usize, allow_end: bool) -> Result<usize, BreakResult> {
+    let Value::Int(index) = func.expect(index, "Int")? else {unreachable!()};
+    let upper = if allow_end { len + 1 } else { len };
+
+    if index < 0 || index >= (upper as i128) {
+        return Err(BreakResult::error(format!(
+            "Index out of bounds: '{}' got index {}, which is not in [0, {}).",
+            func.toString(), index, upper
+        )));
+    }
+
+    Ok(index as usize)
+}
+
+pub struct Map; 
+
+impl Func for Map { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("map")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'map' takes 2 arguments (list, function), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let func = self.expect(input_args[1].clone(), "Call")?;
+
+        let items = lst.borrow().clone();
+        let mut mapped = Vec::with_capacity(items.len());
+        for item in items {
+            mapped.push(interpreter.call_value(&func, vec![item])?);
+        }
+
+        return Ok(Value::List(Rc::new(RefCell::new(mapped))));
+    }
+}
+
+pub struct Filter; 
+
+impl Func for Filter { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("filter")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'filter' takes 2 arguments (list, function), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let func = self.expect(input_args[1].clone(), "Call")?;
+
+        let items = lst.borrow().clone();
+        let mut kept = Vec::new();
+        for item in items {
+            let keep = interpreter.call_value(&func, vec![item.clone()])?;
+            if interpreter.to_bool(&keep) {
+                kept.push(item);
+            }
+        }
+
+        return Ok(Value::List(Rc::new(RefCell::new(kept))));
+    }
+}
+
+pub struct Reduce; 
+
+impl Func for Reduce { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("reduce")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 && input_args.len() != 3 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'reduce' takes 2 or 3 arguments (list, function, initial), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let func = self.expect(input_args[1].clone(), "Call")?;
+
+        let mut items = lst.borrow().clone().into_iter();
+
+        // Without an initial value the first element seeds the accumulator
+        let mut acc = match input_args.get(2) {
+            Some(initial) => initial.clone(),
+            None => match items.next() {
+                Some(first) => first,
+                None => return Err(BreakResult::error("Value error: 'reduce' of an empty list needs an initial value.")),
+            },
+        };
+
+        for item in items {
+            acc = interpreter.call_value(&func, vec![acc, item])?;
+        }
+
+        return Ok(acc);
+    }
+}
+
+pub struct Sort; 
+
+impl Func for Sort { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("sort")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 && input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'sort' takes 1 or 2 arguments (list, key or comparator), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let items = lst.borrow().clone();
+
+        let sorted = match input_args.get(1) {
+            None => merge_sort(items, &mut |a, b| compare_values(&interpreter, a, b))?,
+            Some(f) => {
+                let Value::Call(callee, _) = self.expect(f.clone(), "Call")? else {unreachable!()};
+
+                if callee.arity() == Some(2) {
+                    // Comparator: negative, zero or positive like `a - b`
+                    merge_sort(items, &mut |a, b| {
+                        match interpreter.call_value(f, vec![a.clone(), b.clone()])? {
+                            Value::Int(n) => Ok(n.cmp(&0)),
+                            Value::Float(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+                            other => Err(BreakResult::error(format!(
+                                "Type error: 'sort' comparator must return a number, got {}.",
+                                interpreter.get_type_name(&other)
+                            ))),
+                        }
+                    })?
+                } else {
+                    // Key function: computed once per element, then compared
+                    let mut keyed = Vec::with_capacity(items.len());
+                    for item in items {
+                        keyed.push((interpreter.call_value(f, vec![item.clone()])?, item));
+                    }
+
+                    merge_sort(keyed, &mut |a, b| compare_values(&interpreter, &a.0, &b.0))?
+                        .into_iter()
+                        .map(|(_, item)| item)
+                        .collect()
+                }
+            }
+        };
+
+        return Ok(Value::List(Rc::new(RefCell::new(sorted))));
+    }
+}
+
+pub struct Reverse; 
+
+impl Func for Reverse { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("reverse")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'reverse' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        let mut reversed = lst.borrow().clone();
+        reversed.reverse();
+
+        return Ok(Value::List(Rc::new(RefCell::new(reversed))));
+    }
+}
+
+pub struct Sum; 
+
+impl Func for Sum { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("sum")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'sum' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        // Stays an Int until the first Float shows up
+        let mut total = Value::Int(0);
+        for item in lst.borrow().iter() {
+            total = match (total, item) {
+                (Value::Int(m), Value::Int(n)) => Value::Int(m + n),
+                (Value::Int(m), Value::Float(n)) => Value::Float(m as f64 + n),
+                (Value::Float(m), Value::Int(n)) => Value::Float(m + *n as f64),
+                (Value::Float(m), Value::Float(n)) => Value::Float(m + n),
+                (_, other) => return Err(BreakResult::error(format!(
+                    "Type error: 'sum' expects a list of numbers, got {:?}.",
+                    other
+                ))),
+            };
+        }
+
+        return Ok(total);
+    }
+}
+
+/// Shared by `min` and `max`: the extreme element of a non-empty list
+fn extreme(func: &dyn Func, interpreter: &Interpreter, input_args: &[Value], wanted: Ordering) -> Result<Value, BreakResult> {
+    if input_args.len() != 1 { 
+        return Err(BreakResult::error_code("D0405", format!(
+            "Arity error: '{}' takes 1 argument, but got {}.",
+            func.toString(),
+            input_args.len()
+        )));
+    }
+
+    let Value::List(lst) = func.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+    let mut items = lst.borrow().clone().into_iter();
+    let Some(mut best) = items.next() else {
+        return Err(BreakResult::error(format!(
+            "Value error: '{}' of an empty list.",
+            func.toString()
+        )));
+    };
+
+    for item in items {
+        if compare_values(interpreter, &item, &best)? == wanted {
+            best = item;
+        }
+    }
+
+    Ok(best)
+}
+
+pub struct Min; 
+
+impl Func for Min { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("min")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        extreme(self, &interpreter, &input_args, Ordering::Less)
+    }
+}
+
+pub struct Max; 
+
+impl Func for Max { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("max")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        extreme(self, &interpreter, &input_args, Ordering::Greater)
+    }
+}
+
+/// Shared by `any` and `all`: tests each element's truthiness, through the predicate if given
+fn quantify(func: &dyn Func, interpreter: &Interpreter, input_args: &[Value], stop_on: bool) -> Result<Value, BreakResult> {
+    if input_args.len() != 1 && input_args.len() != 2 { 
+        return Err(BreakResult::error_code("D0405", format!(
+            "Arity error: '{}' takes 1 or 2 arguments (list, predicate), but got {}.",
+            func.toString(),
+            input_args.len()
+        )));
+    }
+
+    let Value::List(lst) = func.expect(input_args[0].clone(), "List")? else {unreachable!()};
+    let predicate = match input_args.get(1) {
+        Some(f) => Some(func.expect(f.clone(), "Call")?),
+        None => None,
+    };
+
+    let items = lst.borrow().clone();
+    for item in items {
+        let tested = match &predicate {
+            Some(f) => interpreter.call_value(f, vec![item])?,
+            None => item,
+        };
+
+        if interpreter.to_bool(&tested) == stop_on {
+            return Ok(Value::Bool(stop_on));
+        }
+    }
+
+    Ok(Value::Bool(!stop_on))
+}
+
+pub struct Any; 
+
+impl Func for Any { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("any")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        quantify(self, &interpreter, &input_args, true)
+    }
+}
+
+pub struct All; 
+
+impl Func for All { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("all")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        quantify(self, &interpreter, &input_args, false)
+    }
+}
+
+pub struct Zip; 
+
+impl Func for Zip { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("zip")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'zip' takes 2 arguments (list, list), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst1) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let Value::List(lst2) = self.expect(input_args[1].clone(), "List")? else {unreachable!()};
+
+        // Stops at the shorter list
+        let pairs: Vec<Value> = lst1.borrow().iter()
+            .zip(lst2.borrow().iter())
+            .map(|(a, b)| Value::List(Rc::new(RefCell::new(vec![a.clone(), b.clone()]))))
+            .collect();
+
+        return Ok(Value::List(Rc::new(RefCell::new(pairs))));
+    }
+}
+
+pub struct Enumerate; 
+
+impl Func for Enumerate { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("enumerate")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'enumerate' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        let pairs: Vec<Value> = lst.borrow().iter()
+            .enumerate()
+            .map(|(i, v)| Value::List(Rc::new(RefCell::new(vec![Value::Int(i as i128), v.clone()]))))
+            .collect();
+
+        return Ok(Value::List(Rc::new(RefCell::new(pairs))));
+    }
+}
+
+pub struct Range; 
+
+impl Func for Range { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("range")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.is_empty() || input_args.len() > 3 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'range' takes 1 to 3 arguments (start, stop, step), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let mut bounds = Vec::new();
+        for arg in input_args.iter() {
+            let Value::Int(n) = self.expect(arg.clone(), "Int")? else {unreachable!()};
+            bounds.push(n);
+        }
+
+        // range(stop), range(start, stop) or range(start, stop, step)
+        let (start, stop, step) = match bounds.as_slice() {
+            [stop] => (0, *stop, 1),
+            [start, stop] => (*start, *stop, 1),
+            [start, stop, step] => (*start, *stop, *step),
+            _ => unreachable!(),
+        };
+
+        if step == 0 {
+            return Err(BreakResult::error("Value error: 'range' step must not be zero."));
+        }
+
+        let len = if (step > 0 && start < stop) || (step < 0 && start > stop) {
+            (stop.abs_diff(start) - 1) / step.unsigned_abs() + 1
+        } else {
+            0
+        };
+
+        if len > MAX_RANGE_LEN {
+            return Err(BreakResult::error(format!(
+                "Value error: 'range' would make a list of {} items, more than the limit of {}.",
+                len, MAX_RANGE_LEN
+            )));
+        }
+
+        let mut numbers = Vec::with_capacity(len as usize);
+        let mut n = Some(start);
+        while let Some(i) = n.filter(|_| numbers.len() < len as usize) {
+            numbers.push(Value::Int(i));
+            n = i.checked_add(step);
+        }
+
+        return Ok(Value::List(Rc::new(RefCell::new(numbers))));
+    }
+}
+
+pub struct IndexOf; 
+
+impl Func for IndexOf { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("index_of")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'index_of' takes 2 arguments (list, value), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        match lst.borrow().iter().position(|v| Interpreter::values_equal(v, &input_args[1])) {
+            Some(i) => return Ok(Value::Int(i as i128)),
+            None => return Ok(Value::Int(-1)),
+        }
+    }
+}
+
+pub struct Contains; 
+
+impl Func for Contains { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("contains")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'contains' takes 2 arguments (list, value), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        // Also works as a substring test
+        if let Value::String(s) = &input_args[0] {
+            let Value::String(sub) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+            return Ok(Value::Bool(s.contains(sub.as_str())));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        return Ok(Value::Bool(lst.borrow().iter().any(|v| Interpreter::values_equal(v, &input_args[1]))));
+    }
+}
+
+pub struct Insert; 
+
+impl Func for Insert { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("insert")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 3 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'insert' takes 3 arguments (list, index, value), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let len = lst.borrow().len();
+        let index = list_index(self, input_args[1].clone(), len, true)?;
+
+        lst.borrow_mut().insert(index, input_args[2].clone());
+
+        return Ok(Value::List(Rc::clone(&lst)));
+    }
+}
+
+pub struct Pop; 
+
+impl Func for Pop { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("pop")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'pop' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        match lst.borrow_mut().pop() {
+            Some(v) => return Ok(v),
+            None => return Err(BreakResult::error("Value error: 'pop' from an empty list.")),
+        }
+    }
+}
+
+pub struct RemoveAt; 
+
+impl Func for RemoveAt { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("remove_at")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'remove_at' takes 2 arguments (list, index), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let len = lst.borrow().len();
+        let index = list_index(self, input_args[1].clone(), len, false)?;
+
+        return Ok(lst.borrow_mut().remove(index));
+    }
+}
+
+pub struct Flatten; 
+
+impl Func for Flatten { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("flatten")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'flatten' takes 1 argument, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        // Only one level deep; non-list elements are kept as they are
+        let mut flat = Vec::new();
+        for item in lst.borrow().iter() {
+            match item {
+                Value::List(inner) => flat.extend(inner.borrow().iter().cloned()),
+                other => flat.push(other.clone()),
+            }
+        }
+
+        return Ok(Value::List(Rc::new(RefCell::new(flat))));
+    }
+}
+
+/// What a finished module exports
+pub struct ModuleExports {
+    pub module: Value,
+}
+
+/// Shared by every interpreter in one run: modules already executed, keyed by canonical
+/// path, the chain of imports currently in progress (for cycle detection), the project's
+/// dependencies (package name to entry file), and the extra directories searched after the
+/// importer's own (`--lib-dir`, then `DINGLE_PATH`)
+#[derive(Default)]
+pub struct ImportState {
+    pub cache: HashMap<PathBuf, Rc<ModuleExports>>,
+    pub chain: Vec<(PathBuf, String)>,
+    pub packages: HashMap<String, PathBuf>,
+    pub search_path: Vec<PathBuf>,
+}
+
+impl ImportState {
+    /// Marks the program's own file as in progress, so a module importing it is a cycle
+    pub fn enter_main(&mut self, file: &str) {
+        if let Ok(canonical) = fs::canonicalize(file) {
+            self.chain.push((canonical, String::from(file)));
+        }
+    }
+}
+
+/// Names declared with `export` at the top level, or `None` if the file never uses `export`
+fn exported_names(statements: &[Statement]) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    let mut any = false;
+
+    for stmt in statements {
+        if let Statement::Export(_, inner) = stmt {
+            any = true;
+            match inner.as_ref() {
+                Statement::Var(name, _) | Statement::Function(name, _, _) => names.push(name.lexeme.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    if any { Some(names) } else { None }
+}
+
+/// Where an import specifier was found
+enum ModuleSource {
+    File(PathBuf),
+    Std(&'static str),
+}
+
+/// `std/list` and `std/list.dingle` name the same module
+fn module_file(spec: &str) -> String {
+    if Path::new(spec).extension().is_some() {
+        String::from(spec)
+    } else {
+        format!("{}.dingle", spec)
+    }
+}
+
+/// Looks for `spec` next to the importer, then among the project's dependencies, then in each
+/// search path directory, then in the bundled standard library. Returns the cache key, a
+/// display name and the source.
+fn find_module(interpreter: &Interpreter, spec: &str, importer: &str) -> Result<(PathBuf, String, ModuleSource), BreakResult> {
+    let file_name = module_file(spec);
+
+    // The REPL has no file, so its imports start from the working directory
+    let mut candidates = vec![Path::new(importer).parent().map(Path::to_path_buf).unwrap_or_default().join(&file_name)];
+
+    // `import("pkg")` is a dependency's entry file, `import("pkg/util")` a file next to it
+    let (package, rest) = spec.split_once('/').unwrap_or((spec, ""));
+    if let Some(entry) = interpreter.imports.borrow().packages.get(package) {
+        if rest.is_empty() {
+            candidates.push(entry.clone());
+        } else {
+            candidates.push(entry.parent().map(Path::to_path_buf).unwrap_or_default().join(module_file(rest)));
+        }
+    }
+
+    let dirs: Vec<PathBuf> = interpreter.imports.borrow().search_path.clone();
+    candidates.extend(dirs.iter().map(|dir| dir.join(&file_name)));
+
+    for path in candidates.iter() {
+        if path.is_file() {
+            let canonical = fs::canonicalize(path).map_err(|e| BreakResult::error_code("D0411", format!(
+                "Import error: could not read '{}': {}.",
+                path.display(), e
+            )))?;
+            return Ok((canonical, path.display().to_string(), ModuleSource::File(path.clone())));
+        }
+    }
+
+    if let Some(name) = file_name.strip_prefix("std/").and_then(|f| f.strip_suffix(".dingle"))
+        && let Some(source) = stdlib::source(name) {
+            let display = format!("std/{}", name);
+            // Not a real path, so it can't collide with a canonical file path
+            return Ok((PathBuf::from(format!("<{}>", display)), display, ModuleSource::Std(source)));
+        }
+
+    let searched: Vec<String> = candidates
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect();
+
+    Err(BreakResult::error_code("D0411", format!(
+        "Import error: could not find '{}' (tried {}, and the standard library).",
+        spec, searched.join(", ")
+    )))
+}
+
+/// Scans, parses, resolves and runs a module in a fresh interpreter that shares our run-wide state
+fn run_module(interpreter: &Interpreter, path: &str, source: ModuleSource) -> Result<ModuleExports, BreakResult> {
+    // The module's own error is reported first, at the failing line, so only name the phase
+    let failed = |phase: &str, cause: Diagnostic| BreakResult::from(Diagnostic::new(Phase::Runtime, format!(
+        "Import error: '{}' failed to {} (see the error above).",
+        path, phase
+    )).with_code("D0411").with_cause(cause));
+
+    let contents = match source {
+        ModuleSource::File(file) => fs::read_to_string(&file).map_err(|e| BreakResult::error_code("D0411", format!(
+            "Import error: could not read '{}': {}.",
+            path, e
+        )))?,
+        ModuleSource::Std(text) => String::from(text),
+    };
+
+    let mut resolver_exe = Resolver::new();
+    // With a call stack of its own: the import errors wrapping the module's error show how it was reached
+    let mut interpreter_exe = Interpreter::with_streams(interpreter.quiet, interpreter.streams.clone());
+    // Imported code draws from the same random stream as the importer
+    interpreter_exe.rng = Rc::clone(&interpreter.rng);
+    interpreter_exe.script_args = Rc::clone(&interpreter.script_args);
+    interpreter_exe.imports = Rc::clone(&interpreter.imports);
+    interpreter_exe.sources = Rc::clone(&interpreter.sources);
+    interpreter_exe.natives = Rc::clone(&interpreter.natives);
+    for (name, func) in interpreter.natives.borrow().iter() {
+        interpreter_exe.define_builtin(name, Rc::clone(func));
+    }
+    interpreter_exe.tree_walker = interpreter.tree_walker;
+    interpreter_exe.max_errors = interpreter.max_errors;
+    interpreter_exe.error_format = interpreter.error_format;
+    #[cfg(feature = "jit")]
+    {
+        interpreter_exe.jit = Rc::clone(&interpreter.jit);
+    }
+
+    let source = interpreter.sources.borrow_mut().add(path, &contents);
+    let tokens = scan(&contents, false, String::from(path), source, interpreter.max_errors)
+        .map_err(|e| failed("scan", e))?;
+
+    let mut parser = Parser::new(tokens, interpreter.max_errors);
+    let mut statements = parser.parse().map_err(|e| failed("parse", e))?;
+
+    resolver_exe.resolve(&mut statements).map_err(|e| failed("resolve", e))?;
+
+    let exported = exported_names(&statements);
+
+    match vm::run_program(&mut interpreter_exe, &statements) {
+        Ok(None) => {},
+        // `exit` inside an imported file ends the whole program
+        Ok(Some(code)) => return Err(BreakResult::Exit(code)),
+        Err(e) => return Err(failed("run", e)),
+    }
+
+    // Without any `export`, every global not starting with '_' is public
+    let members = Rc::new(RefCell::new(Environment::new(None)));
+    for (k, v) in interpreter_exe.global_environment.borrow().hashMap.iter() {
+        let public = match &exported {
+            Some(names) => names.contains(k),
+            None => !k.starts_with('_'),
+        };
+
+        if public {
+            members.borrow_mut().define_default(k, v.clone());
+        }
+    }
+
+    let name = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from(path));
+
+    Ok(ModuleExports { module: Value::Module(name, members) })
+}
+
+/// Runs (at most once per run) the module `spec`, imported from the file `importer`, and returns it
+pub fn load_module(interpreter: &Interpreter, spec: &str, importer: &str) -> Result<Value, BreakResult> {
+    let (canonical, display, source) = find_module(interpreter, spec, importer)?;
+
+    let cached = interpreter.imports.borrow().cache.get(&canonical).cloned();
+    let exports = match cached {
+        Some(exports) => exports,
+        None => {
+            let cycle_start = interpreter.imports.borrow().chain.iter().position(|(p, _)| *p == canonical);
+            if let Some(start) = cycle_start {
+                let mut names: Vec<String> = interpreter.imports.borrow().chain[start..]
+                    .iter()
+                    .map(|(_, name)| name.clone())
+                    .collect();
+                names.push(display);
+
+                return Err(BreakResult::error_code("D0411", format!(
+                    "Import error: import cycle detected: {}.",
+                    names.join(" -> ")
+                )));
+            }
+
+            interpreter.imports.borrow_mut().chain.push((canonical.clone(), display.clone()));
+            let result = run_module(interpreter, &display, source);
+            interpreter.imports.borrow_mut().chain.pop();
+
+            let exports = Rc::new(result?);
+            interpreter.imports.borrow_mut().cache.insert(canonical, Rc::clone(&exports));
+            exports
+        }
+    };
+
+    Ok(exports.module.clone())
+}
+
+pub struct Import; 
+
+impl Func for Import { 
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("import")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+
+        if input_args.len() != 1 { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'import' takes 1 arguments String, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(spec) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        // Relative to the file containing the import call
+        let importer = interpreter.call_site.as_ref().map(|t| t.file.clone()).unwrap_or_default();
+
+        load_module(&interpreter, &spec, &importer)
+    }
+}
+
+pub struct Function{
+    pub name: Token, 
+    pub args_list: Vec<Token>, 
+    pub statement_list: Rc<[Statement]>
+}
+
+impl Func for Function {
+
+    fn isDefault(&self) -> bool {
+        false
+    }
+
+    fn toString(&self) -> String {
+        return self.name.lexeme.clone();
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.args_list.len())
+    }
+
+    fn call(&self, mut interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>  {
+        if input_args.len() != self.args_list.len() { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: function '{}' expects {} argument(s), but got {}.",
+                self.name.lexeme,
+                self.args_list.len(),
+                input_args.len()
+            )));
+        }
+
+        #[cfg(feature = "jit")]
+        if let Some(result) = interpreter.jit.borrow_mut().call(
+            jit::Callee {
+                name: Some(&self.name),
+                params: &self.args_list,
+                body: &self.statement_list,
+                env: &interpreter.global_environment,
+                this: self as *const Function as *const (),
+            },
+            &input_args,
+        ) {
+            return Ok(result);
+        }
+
+        interpreter.call_function(&self.statement_list, input_args)
+    }
+    
+}
+
+pub struct Lambda{
+    pub args_list: Vec<Token>, 
+    pub statement_list: Rc<[Statement]>
+}
+
+impl Func for Lambda {
+
+    fn isDefault(&self) -> bool {
+        false
+    }
+
+    fn toString(&self) -> String {
+        let mut text = String::from("Lambda(");
+
+        for i in 0..self.args_list.len(){
+            
+            text += &self.args_list[i].lexeme ; 
+
+            if i != self.args_list.len() -1 {
+                text += ",";
+            }
+        }
+
+        text += ")";
+
+        return text; 
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.args_list.len())
+    }
+
+    fn call(&self, mut interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>  {
+        if input_args.len() != self.args_list.len() { 
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: lambda expects {} argument(s), but got {}.",
+                self.args_list.len(),
+                input_args.len()
+            )));
+        }
+
+        #[cfg(feature = "jit")]
+        if let Some(result) = interpreter.jit.borrow_mut().call(
+            jit::Callee {
+                name: None,
+                params: &self.args_list,
+                body: &self.statement_list,
+                env: &interpreter.global_environment,
+                this: self as *const Lambda as *const (),
+            },
+            &input_args,
+        ) {
+            return Ok(result);
+        }
+
+        interpreter.call_function(&self.statement_list, input_args)
+    }
     
 }
@@ -0,0 +1,123 @@
+use crate::ast::{self, BreakResult};
+use crate::func::Func;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::fmt;
+use std::rc::Rc;
+
+/// A Rust object handed to scripts as it is, e.g. a database handle or a game entity. Scripts read
+/// and write its properties and call its methods with `.`; everything else about it stays in Rust.
+///
+/// Methods take `&self`, so state a script can change goes in a `Cell` or `RefCell`:
+///
+/// ```
+/// use dinglebob::{Engine, HostObject, Value};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// struct Counter(Cell<i128>);
+///
+/// impl HostObject for Counter {
+///     fn type_name(&self) -> &str {
+///         "Counter"
+///     }
+///
+///     fn get(&self, name: &str) -> Option<Value> {
+///         (name == "count").then(|| Value::Int(self.0.get()))
+///     }
+///
+///     fn has_method(&self, name: &str) -> bool {
+///         name == "bump"
+///     }
+///
+///     fn call_method(&self, _name: &str, _args: Vec<Value>) -> Result<Value, String> {
+///         self.0.set(self.0.get() + 1);
+///         Ok(Value::None)
+///     }
+/// }
+///
+/// let counter = Rc::new(Counter(Cell::new(0)));
+/// let mut engine = Engine::new();
+/// engine.set_global("counter", Value::Native(counter.clone()));
+/// engine.eval("counter.bump(); counter.bump();").unwrap();
+///
+/// assert_eq!(engine.eval("counter.count;"), Ok(Value::Int(2)));
+/// assert_eq!(engine.eval("type(counter);"), Ok(Value::String(String::from("Counter"))));
+/// assert_eq!(counter.0.get(), 2);
+/// ```
+pub trait HostObject {
+    /// The name `type()` gives it, and `Func::expect` checks for
+    fn type_name(&self) -> &str;
+
+    /// `object.name`; None if it has no such property
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    /// `object.name = value`; the error becomes a runtime error
+    fn set(&self, name: &str, _value: Value) -> Result<(), String> {
+        Err(format!("Type error: {} has no property '{}' to set.", self.type_name(), name))
+    }
+
+    /// Whether `object.name(...)` is a method call
+    fn has_method(&self, _name: &str) -> bool {
+        false
+    }
+
+    /// Runs a method `has_method` accepted; the error becomes a runtime error
+    fn call_method(&self, name: &str, _args: Vec<Value>) -> Result<Value, String> {
+        Err(format!("Type error: {} has no method '{}'.", self.type_name(), name))
+    }
+
+    /// How `print` shows it; `<TypeName>` if None
+    fn display(&self) -> Option<String> {
+        None
+    }
+}
+
+impl dyn HostObject {
+    /// The same object, not merely an equal-looking one
+    pub(crate) fn same(a: &Rc<dyn HostObject>, b: &Rc<dyn HostObject>) -> bool {
+        std::ptr::addr_eq(Rc::as_ptr(a), Rc::as_ptr(b))
+    }
+
+    pub(crate) fn to_display(&self) -> String {
+        self.display().unwrap_or_else(|| format!("<{}>", self.type_name()))
+    }
+}
+
+impl fmt::Debug for dyn HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Native(<{}>)", self.type_name())
+    }
+}
+
+impl PartialEq for dyn HostObject {
+    fn eq(&self, other: &dyn HostObject) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+/// `object.name` for a method: calling it calls the method on the object
+pub struct HostMethod {
+    pub object: Rc<dyn HostObject>,
+    pub name: String,
+}
+
+impl Func for HostMethod {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        format!("{}.{}", self.object.type_name(), self.name)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<ast::Value>) -> Result<ast::Value, BreakResult> {
+        let args = input_args.iter().map(Value::from_internal).collect();
+        match self.object.call_method(&self.name, args) {
+            Ok(value) => Ok(value.into_internal()),
+            Err(msg) => Err(BreakResult::error(msg)),
+        }
+    }
+}
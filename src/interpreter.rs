@@ -1,45 +1,85 @@
 use crate::ast::Expression;
+use crate::ast::Local;
 use crate::ast::Value;
 use crate::ast::Statement; 
 use crate::ast::BreakResult;
 use crate::func;
-use crate::repl;
+use crate::math;
+use crate::fileio;
+use crate::system;
+use crate::plugin;
+use crate::stream::Streams;
+use crate::host::{HostMethod, HostObject};
+use crate::random;
+use crate::random::Rng;
 use crate::token::TokenKind; 
 use crate::token::Token; 
 use crate::environment::Environment; 
-use core::borrow;
-use std::cell::Ref;
-use std::clone;
-use std::collections::HashMap;
-use std::env::args;
-use std::hash::Hash;
-use std::process; 
+#[cfg(feature = "jit")]
+use crate::jit::Jit;
+ 
 use std::rc::Rc; 
 use std::cell::RefCell; 
+use std::collections::HashMap;
 use crate::func::*; 
-use ariadne::{Color, ColorGenerator, Label, Report, ReportKind, Source};
-use std::fs;
+use crate::diagnostic::{Diagnostic, ErrorFormat, Frame, Phase, Span, MAX_ERRORS};
+use crate::source::{SourceId, SourceMap};
+use std::io::Write;
 
 pub struct Interpreter {
     pub global_environment: Rc<RefCell<Environment>>,
     pub is_prime: bool, 
-    pub locals: Rc<RefCell<HashMap<Token, i32>>>,
-    pub repl: bool
+    /// Print no reports of its own; errors are only returned (`Engine::new()`)
+    pub quiet: bool,
+    /// How reports are written, when they are
+    pub error_format: ErrorFormat,
+    pub rng: Rc<RefCell<Rng>>,
+    pub script_args: Rc<Vec<String>>,
+    /// Environment variables the program set with `set_env`, which `env` reads before the
+    /// process's own; the process environment itself is never written, as a host thread may be reading it
+    pub env_vars: Rc<RefCell<HashMap<String, String>>>,
+    pub imports: Rc<RefCell<ImportState>>,
+    /// The text of everything scanned this run, which reports quote from
+    pub sources: Rc<RefCell<SourceMap>>,
+    /// Built-ins the host registered, which every imported module's prelude gets too
+    pub natives: Rc<RefCell<Natives>>,
+    /// Run programs on the tree-walker even when they compile to bytecode
+    pub tree_walker: bool,
+    /// How many errors scanning or parsing one file reports at most
+    pub max_errors: usize,
+    /// Where `print`, `input` and error reports go; shared by every frame and module
+    pub streams: Streams,
+    /// Call counts and native code for hot functions, shared by every frame and module
+    #[cfg(feature = "jit")]
+    pub jit: Rc<RefCell<Jit>>,
+    /// The call expression that created this frame, when there is one
+    pub call_site: Option<Token>,
+    /// Every call in progress, outermost first, with its parenthesis; shared by every frame and module
+    pub call_stack: Rc<RefCell<Vec<ActiveCall>>>,
+}
+
+/// Built-ins the host registered, by name
+pub type Natives = Vec<(String, Rc<dyn Func>)>;
+
+/// A call in progress: the function, and where its call's parenthesis is. Kept small, as every
+/// call pushes one.
+pub struct ActiveCall {
+    func: Rc<dyn Func>,
+    source: SourceId,
+    start: i32,
+    end: i32,
+    line: i32,
 }
 
 impl Interpreter {
 
-    pub fn clone(&mut self, locals: Rc<RefCell<HashMap<Token, i32>>> ) -> Self {
-        Interpreter {
-            global_environment: Rc::new(RefCell::new(self.global_environment.borrow().clone())),
-            is_prime: self.is_prime, 
-            locals: locals,
-            repl: self.repl
-        }
+    pub fn new(quiet: bool) -> Self {
+        Interpreter::with_streams(quiet, Streams::process())
     }
 
-    pub fn new(is_prime: bool, locals: Rc<RefCell<HashMap<Token, i32>>>, repl: bool ) -> Self {
-        let mut environment = Rc::new(RefCell::new(Environment::new(None, repl))); 
+    pub fn with_streams(quiet: bool, streams: Streams) -> Self {
+        // Built-ins live in a prelude above the globals, so a script's own names can shadow them
+        let environment = Rc::new(RefCell::new(Environment::new(None))); 
         let mut edittable_env = environment.borrow_mut();
 
         // Built-in functions 
@@ -53,21 +93,108 @@ impl Interpreter {
         define("timeit", Box::new(Timeit {}));
         define("abs", Box::new(Abs {}));
         define("len", Box::new(Len {}));
+        define("type", Box::new(Type {}));
         define("copy", Box::new(Copy {}));
         define("append", Box::new(Append {}));
         define("concat", Box::new(Concat {}));
+
+        // String library
+        define("split", Box::new(Split {}));
+        define("join", Box::new(Join {}));
+        define("trim", Box::new(Trim {}));
+        define("upper", Box::new(Upper {}));
+        define("lower", Box::new(Lower {}));
+        define("starts_with", Box::new(StartsWith {}));
+        define("ends_with", Box::new(EndsWith {}));
+        define("find", Box::new(Find {}));
+        define("replace", Box::new(Replace {}));
+        define("repeat", Box::new(Repeat {}));
+        define("chars", Box::new(Chars {}));
+        define("ord", Box::new(Ord {}));
+        define("chr", Box::new(Chr {}));
+        define("pad_left", Box::new(PadLeft {}));
+        define("pad_right", Box::new(PadRight {}));
+        define("format", Box::new(Format {}));
+
+        // List library
+        define("map", Box::new(Map {}));
+        define("filter", Box::new(Filter {}));
+        define("reduce", Box::new(Reduce {}));
+        define("sort", Box::new(Sort {}));
+        define("reverse", Box::new(Reverse {}));
+        define("sum", Box::new(Sum {}));
+        define("min", Box::new(Min {}));
+        define("max", Box::new(Max {}));
+        define("any", Box::new(Any {}));
+        define("all", Box::new(All {}));
+        define("zip", Box::new(Zip {}));
+        define("enumerate", Box::new(Enumerate {}));
+        define("range", Box::new(Range {}));
+        define("index_of", Box::new(IndexOf {}));
+        define("contains", Box::new(Contains {}));
+        define("insert", Box::new(Insert {}));
+        define("pop", Box::new(Pop {}));
+        define("remove_at", Box::new(RemoveAt {}));
+        define("flatten", Box::new(Flatten {}));
+
+        // Input / output
+        define("input", Box::new(fileio::Input {}));
+        define("read_line", Box::new(fileio::ReadLine {}));
+        define("read_file", Box::new(fileio::ReadFile {}));
+        define("read_lines", Box::new(fileio::ReadLines {}));
+        define("write_file", Box::new(fileio::WriteFile {}));
+        define("append_file", Box::new(fileio::AppendFile {}));
+        define("file_exists", Box::new(fileio::FileExists {}));
+        define("list_dir", Box::new(fileio::ListDir {}));
+        define("mkdir", Box::new(fileio::Mkdir {}));
+        define("remove", Box::new(fileio::Remove {}));
+        define("open", Box::new(fileio::Open {}));
+
+        // Process
+        define("args", Box::new(system::Args {}));
+        define("env", Box::new(system::Env {}));
+        define("set_env", Box::new(system::SetEnv {}));
+        define("exit", Box::new(system::Exit {}));
+
         define("import", Box::new(Import {}));
+        define("load_plugin", Box::new(plugin::LoadPlugin {}));
+
+        // Namespaced modules
+        edittable_env.define_default("math", math::module(&environment));
+        edittable_env.define_default("random", random::module(&environment));
+        drop(edittable_env);
+
+        let globals = Environment::new(Some(Rc::clone(&environment)));
 
         Interpreter {
-            global_environment: Rc::clone(&environment),
+            global_environment: Rc::new(RefCell::new(globals)),
             is_prime: true,
-            locals: locals,
-            repl: repl
+            quiet,
+            error_format: ErrorFormat::Human,
+            rng: Rc::new(RefCell::new(Rng::from_time())),
+            script_args: Rc::new(Vec::new()),
+            env_vars: Rc::new(RefCell::new(HashMap::new())),
+            imports: Rc::new(RefCell::new(ImportState::default())),
+            sources: Rc::new(RefCell::new(SourceMap::new())),
+            natives: Rc::new(RefCell::new(Vec::new())),
+            tree_walker: false,
+            max_errors: MAX_ERRORS,
+            streams,
+            #[cfg(feature = "jit")]
+            jit: Rc::new(RefCell::new(Jit::default())),
+            call_site: None,
+            call_stack: Rc::new(RefCell::new(Vec::new())),
         }
     }
 
+    /// Adds a function to the prelude, next to the built-ins defined above
+    pub fn define_builtin(&self, name: &str, func: Rc<dyn Func>) {
+        let value = Value::Call(func, Rc::clone(&self.global_environment));
+        self.global_environment.borrow_mut().define_builtin(name, value);
+    }
+
     /// Helper to get a human-readable string of a Value's type
-    fn get_type_name(&self, val: &Value) -> String {
+    pub fn get_type_name(&self, val: &Value) -> String {
         match val {
             Value::Int(_) => "Int".to_string(),
             Value::Float(_) => "Float".to_string(),
@@ -76,57 +203,60 @@ impl Interpreter {
             Value::None => "None".to_string(),
             Value::List(_) => "List".to_string(),
             Value::Call(_, _) => "Function".to_string(),
+            Value::Module(_, _) => "Module".to_string(),
+            Value::Native(object) => object.type_name().to_string(),
         }
     }
 
-    pub fn prime_interpret(&mut self, statements: Vec<Statement>) -> Result<(), String> {
+    /// Runs a whole program. `Ok(Some(code))` means the program called `exit(code)`.
+    pub fn prime_interpret(&mut self, statements: &[Statement]) -> Result<Option<i32>, Diagnostic> {
         let interpret_result = self.interpret(statements);
+        self.finish(interpret_result)
+    }
+
+    /// Turns how a whole program ended into its outcome, including a stray `return` or `break`
+    pub fn finish(&self, interpret_result: Result<Value, BreakResult>) -> Result<Option<i32>, Diagnostic> {
         match interpret_result {
-            Err(BreakResult::Error(msg)) => return Err(format!("{}", msg)),
+            Err(BreakResult::Error(mut diagnostic)) => {
+                // Only a built-in the host called directly escapes without the code its call adds
+                diagnostic.code.get_or_insert_with(|| String::from("D0420"));
+                Err(*diagnostic)
+            }
+            Err(BreakResult::Exit(code)) => Ok(Some(code)),
             Err(BreakResult::Return(t, _val)) => {
-                self.handle_error(
-                    "'return' can only be used inside a function body.",
-                    t
-                );
-
-                return Err(String::from("Interpreter Error: 'return' can only be used inside a function body."));
+                Err(Diagnostic::at(Phase::Runtime, "'return' can only be used inside a function body.", &t).with_code("D0409"))
             },
             Err(BreakResult::Break(t)) => {
-                self.handle_error(
-                    "'break' can only be used inside a loop body.",
-                    t
-                );
-                
-                return Err(String::from("Interpretter Error: 'break' can only be used inside a loop body."));
+                Err(Diagnostic::at(Phase::Runtime, "'break' can only be used inside a loop body.", &t).with_code("D0410"))
             },
-            _ => {}
+            _ => Ok(None)
         }
-        Ok(())
     }
 
-    pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<Value, BreakResult> {
+    pub fn interpret(&mut self, statements: &[Statement]) -> Result<Value, BreakResult> {
         for stmt in statements {
             self.execute(stmt)?;
         }
         Ok(Value::None)
     }
 
-    fn execute(&mut self, stmt: Statement) -> Result<Value, BreakResult> {
+    fn execute(&mut self, stmt: &Statement) -> Result<Value, BreakResult> {
         match stmt {
             Statement::Expression(exp) => self.evaluate(exp),
-            Statement::If(exp, then_s, else_s) => self.execute_if(exp, *then_s, *else_s),
-            Statement::Function(t, vt, vs) => self.execute_function(t, vt, *vs),
+            Statement::If(exp, then_s, else_s) => self.execute_if(exp, then_s, else_s),
+            Statement::Function(t, vt, vs) => self.execute_function(t, vt, vs),
             Statement::Print(exp) => self.execute_print(exp),     
             Statement::Return(t, val) => self.execute_return(t, val),
             Statement::Var(var, value) => self.execute_var(var, value),
-            Statement::Block(statements) => self.execute_block(*statements),
-            Statement::While(exp, s) => self.execute_while(exp, *s),
+            Statement::Block(statements) => self.execute_block(statements),
+            Statement::While(exp, s) => self.execute_while(exp, s),
             Statement::Break(t) => self.execute_break(t),
-            _ => unreachable!()
+            Statement::Export(_, stmt) => self.execute(stmt),
+            Statement::FromImport(path, names) => self.execute_from_import(path, names),
         }
     }
 
-    fn to_bool(&self, val: &Value) -> bool {
+    pub fn to_bool(&self, val: &Value) -> bool {
         match *val { 
             Value::Bool(t) => t,
             Value::Int(n) => n != 0,
@@ -136,7 +266,26 @@ impl Interpreter {
         }
     }
 
-    fn execute_if(&mut self, exp: Expression, then_s: Statement, else_s: Statement) -> Result<Value, BreakResult> {
+    pub fn execute_from_import(&mut self, path: &Token, names: &[(Token, Token)]) -> Result<Value, BreakResult> {
+        let Value::Module(module_name, members) = func::load_module(self, &path.lexeme, &path.file)? else {unreachable!()};
+
+        for (name, alias) in names {
+            let member = members.borrow().hashMap.get(&name.lexeme).cloned();
+            let Some(value) = member else {
+                return Err(self.handle_error(
+                    "D0408",
+                    &format!("Module '{}' has no export '{}'.", module_name, name.lexeme),
+                    name.clone()
+                ));
+            };
+
+            self.global_environment.borrow_mut().define(alias.clone(), value)?;
+        }
+
+        Ok(Value::None)
+    }
+
+    fn execute_if(&mut self, exp: &Expression, then_s: &Statement, else_s: &Statement) -> Result<Value, BreakResult> {
         let val = self.evaluate(exp)?;
         if self.to_bool(&val) {
             self.execute(then_s)
@@ -145,41 +294,69 @@ impl Interpreter {
         }
     }
 
-    fn execute_function(&mut self, t: Token, vt: Vec<Token>, vs: Vec<Statement>) -> Result<Value, BreakResult> {
-        let t_clone = t.clone();
+    fn execute_function(&mut self, t: &Token, vt: &[Token], vs: &Rc<[Statement]>) -> Result<Value, BreakResult> {
         let function_call = Function {
-            name: t, args_list: vt, statement_list: vs
+            name: t.clone(), args_list: vt.to_vec(), statement_list: Rc::clone(vs)
         };
-        self.global_environment.borrow_mut().define(t_clone, Value::Call(Rc::new(function_call), Rc::clone(&self.global_environment)))
+        self.global_environment.borrow_mut().define(t.clone(), Value::Call(Rc::new(function_call), Rc::clone(&self.global_environment)))
     }
 
-    fn execute_print(&mut self, expression: Expression) -> Result<Value, BreakResult> {
+    fn execute_print(&mut self, expression: &Expression) -> Result<Value, BreakResult> {
         let value = self.evaluate(expression)?; 
+        self.print(&value)?;
+        Ok(Value::None)
+    }
+
+    /// Writes a Value to this program's stdout, the way `print` shows it
+    pub fn print(&self, value: &Value) -> Result<(), BreakResult> {
+        writeln!(self.streams.stdout.borrow_mut(), "{}", Interpreter::stringify(value))
+            .map_err(|e| BreakResult::error(format!("IO error: could not write to stdout: {}.", e)))
+    }
+
+    /// Renders a Value the same way `print` shows it
+    pub fn stringify(value: &Value) -> String {
         match value { 
-            Value::Int(m) => println!("{}", m),
-            Value::Float(m) => println!("{}", m),
-            Value::Bool(m) => println!("{}", m),
-            Value::None => println!("none"),
-            Value::String(m) => println!("{}", m),
-            Value::Call(callee, _) => println!("<fn {}>", callee.toString()),
-            Value::List(vec) => println!("{:?}", vec.borrow_mut())
+            Value::Int(m) => format!("{}", m),
+            Value::Float(m) => format!("{}", m),
+            Value::Bool(m) => format!("{}", m),
+            Value::None => String::from("none"),
+            Value::String(m) => m.clone(),
+            Value::Call(callee, _) => format!("<fn {}>", callee.toString()),
+            Value::List(vec) => format!("{:?}", vec.borrow()),
+            Value::Module(name, _) => format!("<module {}>", name),
+            Value::Native(object) => object.to_display(),
         }
-        Ok(Value::None)
     }
 
-    fn execute_return(&mut self, t: Token, val: Expression) -> Result<Value, BreakResult> {
+    fn execute_return(&mut self, t: &Token, val: &Expression) -> Result<Value, BreakResult> {
         let val_ev = self.evaluate(val)?;
-        Err(BreakResult::Return(t, val_ev))
+        Err(BreakResult::Return(t.clone(), val_ev))
     }
 
-    fn execute_var(&mut self, var: Token, value: Expression) -> Result<Value, BreakResult> { 
+    fn execute_var(&mut self, var: &Token, value: &Expression) -> Result<Value, BreakResult> { 
         let evaluated_var = self.evaluate(value)?; 
-        self.global_environment.borrow_mut().define(var, evaluated_var)
+        self.global_environment.borrow_mut().define(var.clone(), evaluated_var)
     }
 
-    fn execute_block(&mut self, statements: Vec<Statement>) -> Result<Value, BreakResult> {
+    /// Runs a user function's body in this (callee) frame: a scope holding the arguments as its
+    /// slots, then the body's own block
+    pub fn call_function(&mut self, body: &[Statement], args: Vec<Value>) -> Result<Value, BreakResult> {
+        let mut params = Environment::frame(Rc::clone(&self.global_environment));
+        for arg in args {
+            params.push(arg);
+        }
+        self.global_environment = Rc::new(RefCell::new(params));
+
+        match self.execute_block(body) {
+            Ok(_) => Ok(Value::None),
+            Err(BreakResult::Return(_t, v)) => Ok(v),
+            Err(br) => Err(br)
+        }
+    }
+
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<Value, BreakResult> {
         let curr_env = Rc::clone(&self.global_environment); 
-        self.global_environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(&self.global_environment)), self.repl)));
+        self.global_environment = Rc::new(RefCell::new(Environment::frame(Rc::clone(&self.global_environment))));
         
         for statement in statements {
             match self.execute(statement) {
@@ -195,86 +372,112 @@ impl Interpreter {
         Ok(Value::None)
     }
 
-    fn execute_while(&mut self, exp: Expression, s: Statement) -> Result<Value, BreakResult> {
+    fn execute_while(&mut self, exp: &Expression, s: &Statement) -> Result<Value, BreakResult> {
 
-        let mut result = self.evaluate(exp.clone())?;
+        let mut result = self.evaluate(exp)?;
         while self.to_bool(&result) {
-            match self.execute(s.clone()) {
+            match self.execute(s) {
                 Err(BreakResult::Break(_t)) => break,
                 Err(br) => return Err(br),
-                _ => {result = self.evaluate(exp.clone())?},
+                _ => {result = self.evaluate(exp)?},
             }
         }
         Ok(Value::None)
     }
 
-    fn execute_break(&mut self, t: Token) -> Result<Value, BreakResult> { 
-        Err(BreakResult::Break(t))
+    fn execute_break(&mut self, t: &Token) -> Result<Value, BreakResult> { 
+        Err(BreakResult::Break(t.clone()))
     }
 
-    fn evaluate(&mut self, expression: Expression) -> Result<Value, BreakResult> {
+    pub fn evaluate(&mut self, expression: &Expression) -> Result<Value, BreakResult> {
         match expression { 
-            Expression::Assign(i, eq, a) => self.evaluate_assign(*i, eq, *a),
+            Expression::Assign(i, eq, a) => self.evaluate_assign(i, eq, a),
             Expression::Binary(l, o, r) => self.evaluate_binary(l, o, r), 
             Expression::Unary(o, r) => self.evaluate_unary(o, r),
-            Expression::Call(callee, paren, args) => self.evaluate_call(*callee, paren, *args),
-            Expression::Logical(l, o, r) => self.evaluate_logical(*l, o, *r),
-            Expression::Literal(v) => Ok(v),
-            Expression::Grouping(exp) => self.evaluate( *exp), 
-            Expression::Variable(t) => self.evaluate_variable(t),
-            Expression::Lambda(args, stmt ) => self.evaluate_lambda(args, *stmt),
-            Expression::Index(ls, rb, i) => self.evaluate_index(*ls, rb, *i),
-            Expression::List(content, t) => self.evaluate_list(*content, t)
+            Expression::Call(callee, paren, args) => self.evaluate_call(callee, paren, args),
+            Expression::Logical(l, o, r) => self.evaluate_logical(l, o, r),
+            Expression::Literal(v) => Ok(v.clone()),
+            Expression::Grouping(exp) => self.evaluate(exp), 
+            Expression::Variable(t, local) => self.evaluate_variable(t, *local),
+            Expression::Lambda(args, stmt ) => self.evaluate_lambda(args, stmt),
+            Expression::Index(ls, rb, i) => self.evaluate_index(ls, rb, i),
+            Expression::List(content, t) => self.evaluate_list(content, t),
+            Expression::Get(object, name) => self.evaluate_get(object, name)
         }
     }
     
-    fn evaluate_assign(&mut self, i: Expression, eq: Token, a: Expression) -> Result<Value, BreakResult> {
+    fn evaluate_assign(&mut self, i: &Expression, eq: &Token, a: &Expression) -> Result<Value, BreakResult> {
         let a_ev = self.evaluate(a)?; 
 
-        if let Expression::Variable(t) = i {
-            self.global_environment.borrow_mut().assign(t, a_ev);
+        if let Expression::Variable(t, local) = i {
+            let mut env = self.global_environment.borrow_mut();
+            let assigned = match local {
+                Some(local) => env.assign_at(local.depth, local.slot, a_ev.clone()),
+                None => false,
+            };
+            if !assigned
+                && let Err(e) = env.assign(t.clone(), a_ev) {
+                    self.report(e);
+                }
             return Ok(Value::None); 
         }
 
         if let Expression::Index(l, t, i) = i {
-            let l_ev = self.evaluate(*l)?;
-            let i_ev = self.evaluate(*i)?;
+            let l_ev = self.evaluate(l)?;
+            let i_ev = self.evaluate(i)?;
+            return self.assign_index(l_ev, t, i_ev, a_ev);
+        };
 
-            let Value::List(ls) = l_ev else {
-                return Err(self.handle_error(
-                    &format!("Invalid assignment: expected a List for indexing, but got {}.", self.get_type_name(&l_ev)),
-                    t
-                ));
-            };
+        if let Expression::Get(object, name) = i {
+            let object_ev = self.evaluate(object)?;
+            return self.set_member(object_ev, name, a_ev);
+        }
 
-            let Value::Int(index) = i_ev else {
-                return Err(self.handle_error(
-                    &format!("Invalid list index: indices must be Int, but got {}.", self.get_type_name(&i_ev)),
-                    t
-                ));
-            };
+        Err(self.handle_error(
+            "D0407",
+            "Invalid assignment target: expected a variable, list index or property.",
+            eq.clone()
+        ))
+    }
 
-            if index < 0 || index >= (ls.borrow().len() as i128) {
-                return Err(self.handle_error(
-                    &format!("Index out of bounds: index {} is not in [0, {}).", index, ls.borrow().len()),
-                    t
-                ));
-            }
+    /// `list[index] = value`, once all three are evaluated
+    pub fn assign_index(&self, l_ev: Value, t: &Token, i_ev: Value, a_ev: Value) -> Result<Value, BreakResult> {
+        let Value::List(ls) = l_ev else {
+            return Err(self.handle_error(
+                "D0404",
+                &format!("Invalid assignment: expected a List for indexing, but got {}.", self.get_type_name(&l_ev)),
+                t.clone()
+            ));
+        };
 
-            ls.borrow_mut()[index as usize] = a_ev; 
-            return Ok(Value::None); 
+        let Value::Int(index) = i_ev else {
+            return Err(self.handle_error(
+                "D0404",
+                &format!("Invalid list index: indices must be Int, but got {}.", self.get_type_name(&i_ev)),
+                t.clone()
+            ));
         };
 
-        Err(self.handle_error(
-            "Invalid assignment target: expected a variable or list index.",
-            eq
-        ))
+        if index < 0 || index >= (ls.borrow().len() as i128) {
+            return Err(self.handle_error(
+                "D0403",
+                &format!("Index out of bounds: index {} is not in [0, {}).", index, ls.borrow().len()),
+                t.clone()
+            ));
+        }
+
+        ls.borrow_mut()[index as usize] = a_ev; 
+        Ok(Value::None)
     }
 
-    fn evaluate_binary(&mut self, l: Box<Expression>, o: Token, r: Box<Expression>) -> Result<Value, BreakResult> {
-        let l_ev = self.evaluate(*l)?;
-        let r_ev = self.evaluate(*r)?;
+    fn evaluate_binary(&mut self, l: &Expression, o: &Token, r: &Expression) -> Result<Value, BreakResult> {
+        let l_ev = self.evaluate(l)?;
+        let r_ev = self.evaluate(r)?;
+        self.binary_op(l_ev, o, r_ev)
+    }
 
+    /// Applies a binary operator to two evaluated operands
+    pub fn binary_op(&self, l_ev: Value, o: &Token, r_ev: Value) -> Result<Value, BreakResult> {
         match o.kind {
             TokenKind::PLUS => match (l_ev.clone(), r_ev.clone()) {
                 (Value::Int(m), Value::Int(n)) => Ok(Value::Int(m + n)),
@@ -285,16 +488,17 @@ impl Interpreter {
                 (Value::String(m), Value::Float(n)) => Ok(Value::String(m + &n.to_string())),
                 (Value::String(m), Value::String(n)) => Ok(Value::String(m + &n)),
                 _ => Err(self.handle_error(
+                    "D0401",
                     &format!("Type error: '+' expects numbers or strings, but got {} and {}.", 
                     self.get_type_name(&l_ev), self.get_type_name(&r_ev)), 
-                    o
+                    o.clone()
                 )),
             },
 
             TokenKind::MINUS | TokenKind::STAR | TokenKind::SLASH | TokenKind::PERCENT => {
                 match (l_ev.clone(), r_ev.clone()) {
                     (Value::Int(m), Value::Int(n)) => {
-                        if o.kind == TokenKind::SLASH && n == 0 { return Err(self.handle_error("Division by zero.", o)); }
+                        if o.kind == TokenKind::SLASH && n == 0 { return Err(self.handle_error("D0402", "Division by zero.", o.clone())); }
                         let res = match o.kind {
                             TokenKind::MINUS => m - n,
                             TokenKind::STAR => m * n,
@@ -304,7 +508,7 @@ impl Interpreter {
                         Ok(Value::Int(res))
                     }
                     (Value::Float(m), Value::Float(n)) => {
-                        if o.kind == TokenKind::SLASH && n == 0.0 { return Err(self.handle_error("Division by zero.", o)); }
+                        if o.kind == TokenKind::SLASH && n == 0.0 { return Err(self.handle_error("D0402", "Division by zero.", o.clone())); }
                         let res = match o.kind {
                             TokenKind::MINUS => m - n,
                             TokenKind::STAR => m * n,
@@ -315,7 +519,7 @@ impl Interpreter {
                     }
                     (Value::Int(m), Value::Float(n)) | (Value::Float(n), Value::Int(m)) => {
                         let m_f = m as f64;
-                        if o.kind == TokenKind::SLASH && n == 0.0 { return Err(self.handle_error("Division by zero.", o)); }
+                        if o.kind == TokenKind::SLASH && n == 0.0 { return Err(self.handle_error("D0402", "Division by zero.", o.clone())); }
                         let res = match o.kind {
                             TokenKind::MINUS => if matches!(l_ev, Value::Int(_)) { m_f - n } else { n - m_f },
                             TokenKind::STAR => m_f * n,
@@ -325,9 +529,10 @@ impl Interpreter {
                         Ok(Value::Float(res))
                     }
                     _ => Err(self.handle_error(
+                        "D0401",
                         &format!("Type error: '{}' expects numeric operands, but got {} and {}.", 
                         o.lexeme, self.get_type_name(&l_ev), self.get_type_name(&r_ev)), 
-                        o
+                        o.clone()
                     )),
                 }
             },
@@ -353,52 +558,66 @@ impl Interpreter {
                         Ok(Value::Bool(res))
                     }
                     _ => Err(self.handle_error(
+                        "D0401",
                         &format!("Type error: Comparison '{}' expects numeric operands of the same type, but got {} and {}.", 
                         o.lexeme, self.get_type_name(&l_ev), self.get_type_name(&r_ev)), 
-                        o
+                        o.clone()
                     )),
                 }
             }
 
             TokenKind::EQUAL_EQUAL | TokenKind::BANG_EQUAL => {
-                let is_eq = match (l_ev, r_ev) {
-                    (Value::Int(m), Value::Int(n)) => m == n,
-                    (Value::Float(m), Value::Float(n)) => m == n,
-                    (Value::Bool(m), Value::Bool(n)) => m == n,
-                    (Value::String(m), Value::String(n)) => m == n,
-                    (Value::None, Value::None) => true,
-                    _ => false,
-                };
+                let is_eq = Interpreter::values_equal(&l_ev, &r_ev);
                 let result = if o.kind == TokenKind::EQUAL_EQUAL { is_eq } else { !is_eq };
                 Ok(Value::Bool(result))
             }
-            _ => Err(self.handle_error("Internal error: unknown binary operator.", o)),
+            _ => Err(self.handle_error("D0499", "Internal error: unknown binary operator.", o.clone())),
         }
     }
 
-    fn evaluate_unary(&mut self, o: Token, r: Box<Expression>) -> Result<Value, BreakResult> {
-        let r_ev = self.evaluate(*r)?; 
+    /// Equality as used by '==' and '!='
+    pub fn values_equal(l: &Value, r: &Value) -> bool {
+        match (l, r) {
+            (Value::Int(m), Value::Int(n)) => m == n,
+            (Value::Float(m), Value::Float(n)) => m == n,
+            (Value::Bool(m), Value::Bool(n)) => m == n,
+            (Value::String(m), Value::String(n)) => m == n,
+            (Value::None, Value::None) => true,
+            (Value::Native(m), Value::Native(n)) => <dyn HostObject>::same(m, n),
+            _ => false,
+        }
+    }
+
+    fn evaluate_unary(&mut self, o: &Token, r: &Expression) -> Result<Value, BreakResult> {
+        let r_ev = self.evaluate(r)?; 
+        self.unary_op(o, r_ev)
+    }
+
+    /// Applies a prefix operator to an evaluated operand
+    pub fn unary_op(&self, o: &Token, r_ev: Value) -> Result<Value, BreakResult> {
         match o.kind {
             TokenKind::MINUS => match r_ev {
                 Value::Int(m) => Ok(Value::Int(-m)),
                 Value::Float(m) => Ok(Value::Float(-m)),
                 _ => Err(self.handle_error(
+                    "D0401",
                     &format!("Type error: unary '-' expects a number, but got {}.", self.get_type_name(&r_ev)), 
-                    o
+                    o.clone()
                 )),
             }
             TokenKind::BANG => match r_ev {
                 Value::Bool(m) => Ok(Value::Bool(!m)),
                 _ => Err(self.handle_error(
+                    "D0401",
                     &format!("Type error: '!' expects a boolean, but got {}.", self.get_type_name(&r_ev)), 
-                    o
+                    o.clone()
                 )),
             }
             _ => Ok(Value::None)
         }
     }
 
-    fn evaluate_call(&mut self, callee: Expression, paren: Token, args: Vec<Expression>) -> Result<Value, BreakResult> {
+    fn evaluate_call(&mut self, callee: &Expression, paren: &Token, args: &[Expression]) -> Result<Value, BreakResult> {
         let callee_ev = self.evaluate(callee)?; 
         let mut processed_args = Vec::new();
 
@@ -408,43 +627,121 @@ impl Interpreter {
 
         match callee_ev {
             Value::Call(call, env) => {
-                let result = call.call(
-                    Interpreter { 
-                        global_environment: Rc::clone(&env), 
-                        is_prime: false, 
-                        locals: Rc::clone(&self.locals),
-                        repl: self.repl
-                    }, 
-                    processed_args
-                );
-                
-                match result {
-                    Ok(v) => Ok(v),
-                    Err(BreakResult::Error(e)) => {
-                        
-                        if call.isDefault() || self.repl {
-                            return Err(self.handle_error(
-                            &format!("Error inside function call '{}': {}", call.toString(), &e),
-                            paren));
-                        } else {
-                            return Err(self.handle_error(
-                            &format!("Error inside function call '{}'", call.toString()),
-                            paren));
-                        }
-                        
-                        
-                    },
-                    Err(a) => Err(a)
+                let mut frame = self.frame(&env);
+                frame.call_site = Some(paren.clone());
+
+                self.enter_call(&call, paren);
+                let result = call.call(frame, processed_args);
+                self.leave_call(result)
+            },
+            _ => Err(self.not_callable(&callee_ev, paren))
+        }
+    }
+
+    /// Records a call on the call stack, until `leave_call`
+    pub fn enter_call(&self, call: &Rc<dyn Func>, paren: &Token) {
+        self.call_stack.borrow_mut().push(ActiveCall {
+            func: Rc::clone(call),
+            source: paren.source,
+            start: paren.id,
+            end: paren.id_end,
+            line: paren.line,
+        });
+    }
+
+    /// Ends the innermost call. An error escaping it keeps the stack as it was where it was raised;
+    /// a built-in's error, which has no place of its own, is placed at the call.
+    pub fn leave_call(&self, result: Result<Value, BreakResult>) -> Result<Value, BreakResult> {
+        let result = match result {
+            Err(BreakResult::Error(mut e)) => {
+                let stack = self.call_stack.borrow();
+                let call = stack.last().unwrap();
+
+                if e.span.is_none() {
+                    e.message = format!("Error inside function call '{}': {}", call.func.toString(), e.message);
+                    e.code.get_or_insert_with(|| String::from("D0420"));
+                    e.span = Some(self.call_span(call));
+                    e.trace = self.trace(&stack[..stack.len() - 1]);
+                } else if e.trace.is_empty() {
+                    e.trace = self.trace(&stack);
                 }
+                Err(BreakResult::Error(e))
+            }
+            other => other,
+        };
+
+        self.call_stack.borrow_mut().pop();
+        result
+    }
+
+    fn trace(&self, stack: &[ActiveCall]) -> Vec<Frame> {
+        stack
+            .iter()
+            .map(|call| Frame { function: call.func.toString(), call_site: self.call_span(call) })
+            .collect()
+    }
+
+    fn call_span(&self, call: &ActiveCall) -> Span {
+        Span {
+            file: String::from(self.sources.borrow().name(call.source)),
+            source: call.source,
+            start: call.start as usize,
+            end: call.end as usize,
+            line: call.line.max(0) as usize,
+        }
+    }
+
+    pub fn not_callable(&self, callee_ev: &Value, paren: &Token) -> BreakResult {
+        self.handle_error(
+            "D0406",
+            &format!("Type error: expected a function to call, but got {}.", self.get_type_name(callee_ev)),
+            paren.clone()
+        )
+    }
+    
+    /// Interpreter a callee runs in: the callee's closure environment plus our shared state
+    pub fn frame(&self, env: &Rc<RefCell<Environment>>) -> Interpreter {
+        Interpreter { 
+            global_environment: Rc::clone(env), 
+            is_prime: false, 
+            quiet: self.quiet,
+            error_format: self.error_format,
+            rng: Rc::clone(&self.rng),
+            script_args: Rc::clone(&self.script_args),
+            env_vars: Rc::clone(&self.env_vars),
+            imports: Rc::clone(&self.imports),
+            sources: Rc::clone(&self.sources),
+            natives: Rc::clone(&self.natives),
+            tree_walker: self.tree_walker,
+            max_errors: self.max_errors,
+            streams: self.streams.clone(),
+            #[cfg(feature = "jit")]
+            jit: Rc::clone(&self.jit),
+            call_site: None,
+            call_stack: Rc::clone(&self.call_stack),
+        }
+    }
+
+    /// Calls a function value from native code, e.g. the callback given to `map`
+    pub fn call_value(&self, callee: &Value, args: Vec<Value>) -> Result<Value, BreakResult> {
+        match callee {
+            // Traced as called from the built-in's own call
+            Value::Call(call, env) => match &self.call_site {
+                Some(paren) => {
+                    self.enter_call(call, paren);
+                    let result = call.call(self.frame(env), args);
+                    self.leave_call(result)
+                }
+                None => call.call(self.frame(env), args),
             },
-            _ => Err(self.handle_error(
-                &format!("Type error: expected a function to call, but got {}.", self.get_type_name(&callee_ev)),
-                paren
-            ))
+            _ => Err(BreakResult::error_code("D0406", format!(
+                "Type error: expected a function to call, but got {}.",
+                self.get_type_name(callee)
+            ))),
         }
     }
     
-    fn evaluate_logical(&mut self, l: Expression, o: Token, r: Expression) -> Result<Value, BreakResult> {
+    fn evaluate_logical(&mut self, l: &Expression, o: &Token, r: &Expression) -> Result<Value, BreakResult> {
         let l_ev = self.evaluate(l)?;
         if o.kind == TokenKind::OR {
             if self.to_bool(&l_ev) { return Ok(l_ev); }
@@ -454,48 +751,79 @@ impl Interpreter {
         self.evaluate(r)
     }
 
-    fn evaluate_variable(&mut self, token: Token) -> Result<Value, BreakResult> {
-        let binding = self.locals.borrow();
-        let Some(steps) = binding.get(&token) else {
-            return self.global_environment.borrow_mut().get(token);
-        };
-        self.global_environment.borrow_mut().get_at(token, *steps)
+    fn evaluate_variable(&mut self, token: &Token, local: Option<Local>) -> Result<Value, BreakResult> {
+        let env = self.global_environment.borrow();
+        if let Some(local) = local
+            && let Some(v) = env.get_at(local.depth, local.slot) {
+                return Ok(v);
+            }
+        // Globals, and a local a closure reached before its declaration ran, go by name
+        env.get(token.clone())
     }
 
-    fn evaluate_lambda(&mut self, args_list: Vec<Token>, bdy: Vec<Statement>) -> Result<Value, BreakResult> {
-        let function_call = Lambda { args_list, statement_list: bdy };
+    fn evaluate_lambda(&mut self, args_list: &[Token], bdy: &Rc<[Statement]>) -> Result<Value, BreakResult> {
+        let function_call = Lambda { args_list: args_list.to_vec(), statement_list: Rc::clone(bdy) };
         Ok(Value::Call(Rc::new(function_call), Rc::clone(&self.global_environment)))
     }
     
-    fn evaluate_index(&mut self, l: Expression, t: Token, i: Expression) -> Result<Value, BreakResult> {
+    fn evaluate_index(&mut self, l: &Expression, t: &Token, i: &Expression) -> Result<Value, BreakResult> {
         let l_ev = self.evaluate(l)?;
         let i_ev = self.evaluate(i)?;
+        self.index_value(l_ev, t, i_ev)
+    }
+
+    /// `value[index]` for an evaluated List or String and index
+    pub fn index_value(&self, l_ev: Value, t: &Token, i_ev: Value) -> Result<Value, BreakResult> {
+        if let Value::String(s) = &l_ev {
+            let Value::Int(index) = i_ev else {
+                return Err(self.handle_error(
+                    "D0404",
+                    &format!("Type error: string index must be an Int, but got {}.", self.get_type_name(&i_ev)),
+                    t.clone()
+                ));
+            };
+
+            // Strings are indexed by character, not by byte
+            let length = s.chars().count();
+            if index < 0 || index >= (length as i128) {
+                return Err(self.handle_error(
+                    "D0403",
+                    &format!("Index out of bounds: index {} is not in [0, {}).", index, length),
+                    t.clone()
+                ));
+            }
+
+            return Ok(Value::String(s.chars().nth(index as usize).unwrap().to_string()));
+        }
 
         let Value::List(ls) = l_ev else {
             return Err(self.handle_error(
-                &format!("Type error: indexing ('[...]') expects a List, but got {}.", self.get_type_name(&l_ev)),
-                t
+                "D0404",
+                &format!("Type error: indexing ('[...]') expects a List or String, but got {}.", self.get_type_name(&l_ev)),
+                t.clone()
             ));
         };
 
         let Value::Int(index) = i_ev else {
             return Err(self.handle_error(
+                "D0404",
                 &format!("Type error: list index must be an Int, but got {}.", self.get_type_name(&i_ev)),
-                t
+                t.clone()
             ));
         };
 
         if index < 0 || index >= (ls.borrow().len() as i128) {
             return Err(self.handle_error(
+                "D0403",
                 &format!("Index out of bounds: index {} is not in [0, {}).", index, ls.borrow().len()),
-                t
+                t.clone()
             ));
         }
 
         Ok(ls.borrow()[index as usize].clone())
     }
 
-    fn evaluate_list(&mut self, content: Vec<Expression>, _t: Token) -> Result<Value, BreakResult> {
+    fn evaluate_list(&mut self, content: &[Expression], _t: &Token) -> Result<Value, BreakResult> {
         let mut list = Vec::new();
         for item in content {
             list.push(self.evaluate(item)?);
@@ -503,30 +831,79 @@ impl Interpreter {
         Ok(Value::List(Rc::new(RefCell::new(list))))
     }
 
-    fn handle_error(&self, msg: &str, token: Token) -> BreakResult {
-        let line = token.line;
-        let file = token.file.clone();
-        let start = token.id as usize;
-        let end = token.id_end as usize;
+    fn evaluate_get(&mut self, object: &Expression, name: &Token) -> Result<Value, BreakResult> {
+        let object_ev = self.evaluate(object)?;
+        self.get_member(object_ev, name)
+    }
 
-        if !self.repl{
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
+    /// `object.name`; only modules and host objects have members
+    pub fn get_member(&self, object_ev: Value, name: &Token) -> Result<Value, BreakResult> {
+        if let Value::Native(object) = &object_ev {
+            if let Some(value) = object.get(&name.lexeme) {
+                return Ok(crate::value::Value::into_internal(value));
+            }
+            if object.has_method(&name.lexeme) {
+                let method = HostMethod { object: Rc::clone(object), name: name.lexeme.clone() };
+                return Ok(Value::Call(Rc::new(method), Rc::clone(&self.global_environment)));
+            }
+            return Err(self.handle_error(
+                "D0408",
+                &format!("{} has no member '{}'.", object.type_name(), name.lexeme),
+                name.clone()
+            ));
+        }
 
-            let src = fs::read_to_string(&file).unwrap_or_else(|_| "<could not read source file>".to_string());
+        let Value::Module(module_name, members) = object_ev else {
+            return Err(self.handle_error(
+                "D0408",
+                &format!("Type error: member access ('.{}') expects a Module, but got {}.", name.lexeme, self.get_type_name(&object_ev)),
+                name.clone()
+            ));
+        };
 
-            Report::build(ReportKind::Error, (&file, (line - 1) as usize..3))
-                .with_message("Interpreter Error")
-                .with_label(
-                    Label::new((&file, start..end))
-                        .with_message(msg)
-                        .with_color(a),
-                )
-                .finish()
-                .print((&file, Source::from(&src)))
-                .unwrap();
+        let member = members.borrow().hashMap.get(&name.lexeme).cloned();
+        match member {
+            Some(v) => Ok(v),
+            None => Err(self.handle_error(
+                "D0408",
+                &format!("Module '{}' has no member '{}'.", module_name, name.lexeme),
+                name.clone()
+            )),
         }
-        
-        BreakResult::Error(format!("[Interpreter Error: {}", msg))
+    }
+
+    /// `object.name = value`; only host objects have properties to set
+    pub fn set_member(&self, object_ev: Value, name: &Token, a_ev: Value) -> Result<Value, BreakResult> {
+        let Value::Native(object) = object_ev else {
+            return Err(self.handle_error(
+                "D0407",
+                &format!("Invalid assignment: '.{}' can only be set on a host object, but got {}.", name.lexeme, self.get_type_name(&object_ev)),
+                name.clone()
+            ));
+        };
+
+        match object.set(&name.lexeme, crate::value::Value::from_internal(&a_ev)) {
+            Ok(()) => Ok(Value::None),
+            Err(msg) => Err(self.handle_error("D0408", &msg, name.clone())),
+        }
+    }
+
+    /// Prints the report for an error that doesn't stop the program
+    pub fn report(&self, result: BreakResult) {
+        if let BreakResult::Error(diagnostic) = result {
+            self.render(&diagnostic);
+        }
+    }
+
+    /// Writes a diagnostic to stderr in the chosen format, unless quiet
+    pub fn render(&self, diagnostic: &Diagnostic) {
+        if !self.quiet {
+            let out = &mut *self.streams.stderr.borrow_mut();
+            let _ = diagnostic.render_as(self.error_format, &self.sources.borrow(), out);
+        }
+    }
+
+    fn handle_error(&self, code: &str, msg: &str, token: Token) -> BreakResult {
+        Diagnostic::at(Phase::Runtime, msg, &token).with_code(code).into()
     }
 }
\ No newline at end of file
@@ -0,0 +1,769 @@
+use crate::ast::{Expression, Statement, Value};
+use crate::environment::Environment;
+use crate::token::{Token, TokenKind};
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{types, AbiParam, Block, FuncRef, InstBuilder, MemFlags, Type};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Linkage, Module};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+type Reg = cranelift_codegen::ir::Value;
+
+/// Calls a function needs before it's worth compiling
+const HOT_CALLS: u32 = 50;
+
+// Status words returned by native code; the payload word holds the value
+const BAIL: i64 = 0;
+const RETURNED_NONE: i64 = 4;
+
+/// Native entry point: the arguments and the result travel as 64-bit words
+type Entry = extern "C" fn(*const i64, *mut i64) -> i64;
+
+/// The only values compiled code handles. Ints are i64: anything wider bails out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Ty {
+    Int,
+    Float,
+    Bool,
+}
+
+impl Ty {
+    fn of(value: &Value) -> Option<Ty> {
+        match value {
+            Value::Int(n) if i64::try_from(*n).is_ok() => Some(Ty::Int),
+            Value::Float(_) => Some(Ty::Float),
+            Value::Bool(_) => Some(Ty::Bool),
+            _ => None,
+        }
+    }
+
+    fn clif(self) -> Type {
+        match self {
+            Ty::Int => types::I64,
+            Ty::Float => types::F64,
+            Ty::Bool => types::I8,
+        }
+    }
+
+    fn status(self) -> i64 {
+        match self {
+            Ty::Int => 1,
+            Ty::Float => 2,
+            Ty::Bool => 3,
+        }
+    }
+}
+
+/// The function being called, as the JIT sees it
+pub struct Callee<'a> {
+    pub name: Option<&'a Token>,
+    pub params: &'a [Token],
+    pub body: &'a Rc<[Statement]>,
+    // Where its own name is looked up, and its identity: a compiled self-call is only
+    // used while the name still refers to this very function
+    pub env: &'a Rc<RefCell<Environment>>,
+    pub this: *const (),
+}
+
+struct Compiled {
+    entry: Entry,
+    uses_self: bool,
+}
+
+struct Profile {
+    // Held so the body's address, the profile's key, can't be reused by another function
+    _body: Rc<[Statement]>,
+    calls: u32,
+    // Per argument types: native code, or None if it can't be compiled or has bailed out
+    versions: HashMap<Vec<Ty>, Option<Compiled>>,
+}
+
+/// Call counts for every user function, and native code for the hot, purely numeric ones
+#[derive(Default)]
+pub struct Jit {
+    module: Option<JITModule>,
+    unsupported_host: bool,
+    profiles: HashMap<usize, Profile>,
+    next_name: u32,
+    pub stats: JitStats,
+}
+
+/// What the JIT did with the calls to hot functions so far; see `Engine::jit_stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// Calls that ran to completion as native code
+    pub native: u64,
+    /// Calls whose native code bailed out, on an error or an Int past 64 bits, and were run again
+    /// by the interpreter
+    pub bailouts: u64,
+    /// Calls the interpreter ran because no native code fits them: a type guard failed on an
+    /// argument, or the body can't be compiled for these argument types
+    pub fallbacks: u64,
+}
+
+/// How a call to a hot function went
+enum Outcome {
+    Returned(Value),
+    Bailed,
+    NoCode,
+}
+
+impl Jit {
+    /// Runs `callee` natively when it's hot and compiled for these argument types. None means the
+    /// caller interprets the call: not hot yet, not compilable, or a guard failed. Compiled bodies
+    /// have no side effects, so a call that bails out part way can simply be run again.
+    pub fn call(&mut self, callee: Callee, args: &[Value]) -> Option<Value> {
+        let key = Rc::as_ptr(callee.body) as *const Statement as usize;
+        if !self.is_hot(key, &callee) {
+            return None;
+        }
+
+        match self.run(key, &callee, args) {
+            Outcome::Returned(value) => {
+                self.stats.native += 1;
+                Some(value)
+            }
+            Outcome::Bailed => {
+                self.stats.bailouts += 1;
+                None
+            }
+            Outcome::NoCode => {
+                self.stats.fallbacks += 1;
+                None
+            }
+        }
+    }
+
+    /// Counts a call, and says whether the function has had enough to be worth compiling
+    fn is_hot(&mut self, key: usize, callee: &Callee) -> bool {
+        let profile = self.profiles.entry(key).or_insert_with(|| Profile {
+            _body: Rc::clone(callee.body),
+            calls: 0,
+            versions: HashMap::new(),
+        });
+
+        profile.calls = profile.calls.saturating_add(1);
+        profile.calls >= HOT_CALLS
+    }
+
+    /// Runs a hot function's native code for these argument types, compiling it first if need be
+    fn run(&mut self, key: usize, callee: &Callee, args: &[Value]) -> Outcome {
+        if args.len() != callee.params.len() {
+            return Outcome::NoCode;
+        }
+        let Some(tys) = args.iter().map(Ty::of).collect::<Option<Vec<Ty>>>() else {
+            return Outcome::NoCode;
+        };
+
+        if !self.profiles[&key].versions.contains_key(&tys) {
+            let compiled = self.compile(callee, &tys);
+            self.profiles.get_mut(&key).unwrap().versions.insert(tys.clone(), compiled);
+        }
+
+        let versions = &mut self.profiles.get_mut(&key).unwrap().versions;
+        let Some(Some(compiled)) = versions.get(&tys) else {
+            return Outcome::NoCode;
+        };
+
+        if compiled.uses_self && !binds_self(callee) {
+            return Outcome::NoCode;
+        }
+
+        let words: Vec<i64> = args
+            .iter()
+            .map(|arg| match arg {
+                Value::Int(n) => *n as i64,
+                Value::Float(f) => f.to_bits() as i64,
+                Value::Bool(b) => *b as i64,
+                _ => unreachable!(),
+            })
+            .collect();
+        let mut payload = 0i64;
+        let status = (compiled.entry)(words.as_ptr(), &mut payload);
+
+        match status {
+            1 => Outcome::Returned(Value::Int(payload as i128)),
+            2 => Outcome::Returned(Value::Float(f64::from_bits(payload as u64))),
+            3 => Outcome::Returned(Value::Bool(payload != 0)),
+            RETURNED_NONE => Outcome::Returned(Value::None),
+            // The interpreter handles whatever made it bail (an error, an overflow past i64) from now on
+            _ => {
+                versions.insert(tys, None);
+                Outcome::Bailed
+            }
+        }
+    }
+
+    fn module(&mut self) -> Option<&mut JITModule> {
+        if self.module.is_none() && !self.unsupported_host {
+            let mut flags = settings::builder();
+            let configured = flags.set("use_colocated_libcalls", "false").is_ok()
+                && flags.set("is_pic", "false").is_ok()
+                && flags.set("opt_level", "speed").is_ok();
+
+            let isa = cranelift_native::builder()
+                .ok()
+                .filter(|_| configured)
+                .and_then(|isa| isa.finish(settings::Flags::new(flags)).ok());
+
+            match isa {
+                Some(isa) => {
+                    let builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+                    self.module = Some(JITModule::new(builder));
+                }
+                None => self.unsupported_host = true,
+            }
+        }
+        self.module.as_mut()
+    }
+
+    fn compile(&mut self, callee: &Callee, params: &[Ty]) -> Option<Compiled> {
+        // A self-call's result type has to be assumed up front, then confirmed by the returns
+        for assumed in [Ty::Int, Ty::Float, Ty::Bool] {
+            match self.try_compile(callee, params, assumed) {
+                Ok(compiled) => return Some(compiled),
+                Err(Reject::WrongReturn) => continue,
+                Err(Reject::Unsupported) => return None,
+            }
+        }
+        None
+    }
+
+    fn try_compile(&mut self, callee: &Callee, params: &[Ty], assumed: Ty) -> Result<Compiled, Reject> {
+        let id = self.next_name;
+        self.next_name += 1;
+        let module = self.module().ok_or(Reject::Unsupported)?;
+
+        let mut inner_sig = module.make_signature();
+        inner_sig.params.extend(params.iter().map(|ty| AbiParam::new(ty.clif())));
+        inner_sig.returns.push(AbiParam::new(types::I64));
+        inner_sig.returns.push(AbiParam::new(types::I64));
+
+        let inner = module
+            .declare_function(&format!("dingle_{}", id), Linkage::Local, &inner_sig)
+            .map_err(|_| Reject::Unsupported)?;
+
+        let mut ctx = module.make_context();
+        ctx.func.signature = inner_sig;
+        let mut builder_ctx = FunctionBuilderContext::new();
+
+        let uses_self = {
+            let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+            let self_ref = module.declare_func_in_func(inner, builder.func);
+            let mut body = FnCompiler::new(&mut builder, callee, params, assumed, self_ref);
+            body.function(callee.body)?;
+
+            if body.uses_self && !body.returns.contains(&assumed) {
+                return Err(Reject::WrongReturn);
+            }
+            let uses_self = body.uses_self;
+            builder.seal_all_blocks();
+            builder.finalize();
+            uses_self
+        };
+
+        module.define_function(inner, &mut ctx).map_err(|_| Reject::Unsupported)?;
+        module.clear_context(&mut ctx);
+
+        let entry = define_entry(module, id, inner, params)?;
+        module.finalize_definitions().map_err(|_| Reject::Unsupported)?;
+
+        let code = module.get_finalized_function(entry);
+        // SAFETY: `entry` was just defined with exactly the `Entry` signature
+        let entry = unsafe { std::mem::transmute::<*const u8, Entry>(code) };
+        Ok(Compiled { entry, uses_self })
+    }
+}
+
+enum Reject {
+    Unsupported,
+    // Compilable, but a self-call returns a different type than was assumed
+    WrongReturn,
+}
+
+/// Whether the function's name, where it was defined, still refers to the function itself
+fn binds_self(callee: &Callee) -> bool {
+    let Some(name) = callee.name else {
+        return false;
+    };
+
+    match callee.env.borrow().lookup(&name.lexeme) {
+        Some(Value::Call(func, _)) => Rc::as_ptr(&func) as *const () == callee.this,
+        _ => false,
+    }
+}
+
+/// The `Entry` wrapper: unpacks the argument words, calls the typed function, stores the payload
+fn define_entry(
+    module: &mut JITModule,
+    id: u32,
+    inner: cranelift_module::FuncId,
+    params: &[Ty],
+) -> Result<cranelift_module::FuncId, Reject> {
+    let pointer = module.target_config().pointer_type();
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(pointer));
+    sig.params.push(AbiParam::new(pointer));
+    sig.returns.push(AbiParam::new(types::I64));
+
+    let entry = module
+        .declare_function(&format!("dingle_{}_entry", id), Linkage::Local, &sig)
+        .map_err(|_| Reject::Unsupported)?;
+
+    let mut ctx = module.make_context();
+    ctx.func.signature = sig;
+    let mut builder_ctx = FunctionBuilderContext::new();
+    {
+        let mut b = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+        let inner_ref = module.declare_func_in_func(inner, b.func);
+
+        let start = b.create_block();
+        b.append_block_params_for_function_params(start);
+        b.switch_to_block(start);
+        let (args_ptr, out_ptr) = (b.block_params(start)[0], b.block_params(start)[1]);
+
+        let args: Vec<Reg> = params
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let offset = (i * 8) as i32;
+                match ty {
+                    Ty::Int => b.ins().load(types::I64, MemFlags::trusted(), args_ptr, offset),
+                    Ty::Float => b.ins().load(types::F64, MemFlags::trusted(), args_ptr, offset),
+                    Ty::Bool => {
+                        let word = b.ins().load(types::I64, MemFlags::trusted(), args_ptr, offset);
+                        b.ins().ireduce(types::I8, word)
+                    }
+                }
+            })
+            .collect();
+
+        let call = b.ins().call(inner_ref, &args);
+        let (status, payload) = (b.inst_results(call)[0], b.inst_results(call)[1]);
+        b.ins().store(MemFlags::trusted(), payload, out_ptr, 0);
+        b.ins().return_(&[status]);
+        b.seal_all_blocks();
+        b.finalize();
+    }
+
+    module.define_function(entry, &mut ctx).map_err(|_| Reject::Unsupported)?;
+    module.clear_context(&mut ctx);
+    Ok(entry)
+}
+
+/// Type-checks and lowers one function body for fixed argument types
+struct FnCompiler<'a, 'b> {
+    b: &'a mut FunctionBuilder<'b>,
+    name: Option<&'a Token>,
+    params: &'a [Token],
+    tys: &'a [Ty],
+    // What a self-call is assumed to return
+    assumed: Ty,
+    self_ref: FuncRef,
+    // Locals in scope, innermost last, the same scopes the resolver numbered
+    scopes: Vec<HashMap<String, (Variable, Ty)>>,
+    next_var: u32,
+    // Exit block of each enclosing loop
+    loops: Vec<Block>,
+    bail: Block,
+    returns: HashSet<Ty>,
+    uses_self: bool,
+}
+
+type Lowered<T> = Result<T, Reject>;
+
+impl<'a, 'b> FnCompiler<'a, 'b> {
+    fn new(b: &'a mut FunctionBuilder<'b>, callee: &'a Callee, tys: &'a [Ty], assumed: Ty, self_ref: FuncRef) -> Self {
+        let bail = b.create_block();
+        FnCompiler {
+            b,
+            name: callee.name,
+            params: callee.params,
+            tys,
+            assumed,
+            self_ref,
+            scopes: Vec::new(),
+            next_var: 0,
+            loops: Vec::new(),
+            bail,
+            returns: HashSet::new(),
+            uses_self: false,
+        }
+    }
+
+    /// Parameters in one scope and the body in the next, as `Interpreter::call_function` runs them
+    fn function(&mut self, body: &[Statement]) -> Lowered<()> {
+        let start = self.b.create_block();
+        self.b.append_block_params_for_function_params(start);
+        self.b.switch_to_block(start);
+
+        let values = self.b.block_params(start).to_vec();
+        let mut params = HashMap::new();
+        for ((name, &ty), value) in self.params.iter().zip(self.tys).zip(values) {
+            let var = self.new_var(ty);
+            self.b.def_var(var, value);
+            params.insert(name.lexeme.clone(), (var, ty));
+        }
+        self.scopes.push(params);
+
+        self.block(body)?;
+        // Falling off the end returns None
+        self.ret(RETURNED_NONE, None);
+
+        self.b.switch_to_block(self.bail);
+        self.ret(BAIL, None);
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> Lowered<()> {
+        self.scopes.push(HashMap::new());
+        for statement in statements {
+            self.stmt(statement)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Statement) -> Lowered<()> {
+        match stmt {
+            Statement::Var(name, value) => {
+                let (value, ty) = self.expr(value)?;
+                let var = self.new_var(ty);
+                self.b.def_var(var, value);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.lexeme.clone(), (var, ty));
+                }
+                Ok(())
+            }
+            // The missing `else` of an `if`
+            Statement::Expression(Expression::Literal(_)) => Ok(()),
+            Statement::Expression(Expression::Assign(target, _, value)) => {
+                let Expression::Variable(name, _) = target.as_ref() else {
+                    return Err(Reject::Unsupported);
+                };
+                let (var, ty) = self.local(name)?;
+                let (value, value_ty) = self.expr(value)?;
+                // A local that changes type stays interpreted
+                if value_ty != ty {
+                    return Err(Reject::Unsupported);
+                }
+                self.b.def_var(var, value);
+                Ok(())
+            }
+            Statement::Expression(exp) => self.expr(exp).map(|_| ()),
+            Statement::If(cond, then_s, else_s) => {
+                let cond = self.condition(cond)?;
+                let (then_b, else_b, merge) = (self.b.create_block(), self.b.create_block(), self.b.create_block());
+                self.b.ins().brif(cond, then_b, &[], else_b, &[]);
+
+                self.b.switch_to_block(then_b);
+                self.stmt(then_s)?;
+                self.b.ins().jump(merge, &[]);
+
+                self.b.switch_to_block(else_b);
+                self.stmt(else_s)?;
+                self.b.ins().jump(merge, &[]);
+
+                self.b.switch_to_block(merge);
+                Ok(())
+            }
+            Statement::While(cond, body) => {
+                let (header, body_b, exit) = (self.b.create_block(), self.b.create_block(), self.b.create_block());
+                self.b.ins().jump(header, &[]);
+
+                self.b.switch_to_block(header);
+                let cond = self.condition(cond)?;
+                self.b.ins().brif(cond, body_b, &[], exit, &[]);
+
+                self.b.switch_to_block(body_b);
+                self.loops.push(exit);
+                self.stmt(body)?;
+                self.loops.pop();
+                self.b.ins().jump(header, &[]);
+
+                self.b.switch_to_block(exit);
+                Ok(())
+            }
+            Statement::Block(statements) => self.block(statements),
+            Statement::Return(_, Expression::Literal(Value::None)) => {
+                self.ret(RETURNED_NONE, None);
+                self.unreachable();
+                Ok(())
+            }
+            Statement::Return(_, value) => {
+                let (value, ty) = self.expr(value)?;
+                self.returns.insert(ty);
+                self.ret(ty.status(), Some((value, ty)));
+                self.unreachable();
+                Ok(())
+            }
+            Statement::Break(_) => {
+                let exit = *self.loops.last().ok_or(Reject::Unsupported)?;
+                self.b.ins().jump(exit, &[]);
+                self.unreachable();
+                Ok(())
+            }
+            _ => Err(Reject::Unsupported),
+        }
+    }
+
+    fn expr(&mut self, exp: &Expression) -> Lowered<(Reg, Ty)> {
+        match exp {
+            Expression::Literal(Value::Int(n)) => {
+                let n = i64::try_from(*n).map_err(|_| Reject::Unsupported)?;
+                Ok((self.b.ins().iconst(types::I64, n), Ty::Int))
+            }
+            Expression::Literal(Value::Float(f)) => Ok((self.b.ins().f64const(*f), Ty::Float)),
+            Expression::Literal(Value::Bool(b)) => Ok((self.b.ins().iconst(types::I8, *b as i64), Ty::Bool)),
+            Expression::Grouping(exp) => self.expr(exp),
+            Expression::Variable(name, _) => {
+                let (var, ty) = self.local(name)?;
+                Ok((self.b.use_var(var), ty))
+            }
+            Expression::Unary(op, right) => self.unary(op, right),
+            Expression::Binary(left, op, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                self.binary(op, left, right)
+            }
+            Expression::Logical(left, op, right) => self.logical(left, op, right),
+            Expression::Call(callee, _, args) => self.self_call(callee, args),
+            _ => Err(Reject::Unsupported),
+        }
+    }
+
+    fn unary(&mut self, op: &Token, right: &Expression) -> Lowered<(Reg, Ty)> {
+        let (value, ty) = self.expr(right)?;
+        match (&op.kind, ty) {
+            (TokenKind::MINUS, Ty::Int) => {
+                let zero = self.b.ins().iconst(types::I64, 0);
+                let (negated, overflow) = self.b.ins().ssub_overflow(zero, value);
+                self.bail_if(overflow);
+                Ok((negated, Ty::Int))
+            }
+            (TokenKind::MINUS, Ty::Float) => Ok((self.b.ins().fneg(value), Ty::Float)),
+            (TokenKind::BANG, Ty::Bool) => Ok((self.b.ins().bxor_imm(value, 1), Ty::Bool)),
+            _ => Err(Reject::Unsupported),
+        }
+    }
+
+    /// `Interpreter::binary_op` for Ints, Floats and Bools; whatever it rejects isn't compiled
+    fn binary(&mut self, op: &Token, (l, lt): (Reg, Ty), (r, rt): (Reg, Ty)) -> Lowered<(Reg, Ty)> {
+        match op.kind {
+            TokenKind::PLUS | TokenKind::MINUS | TokenKind::STAR | TokenKind::SLASH | TokenKind::PERCENT => {
+                self.arithmetic(&op.kind, (l, lt), (r, rt))
+            }
+            TokenKind::GREATER | TokenKind::GREATER_EQUAL | TokenKind::LESS | TokenKind::LESS_EQUAL => {
+                let (int_cc, float_cc) = match op.kind {
+                    TokenKind::GREATER => (IntCC::SignedGreaterThan, FloatCC::GreaterThan),
+                    TokenKind::GREATER_EQUAL => (IntCC::SignedGreaterThanOrEqual, FloatCC::GreaterThanOrEqual),
+                    TokenKind::LESS => (IntCC::SignedLessThan, FloatCC::LessThan),
+                    _ => (IntCC::SignedLessThanOrEqual, FloatCC::LessThanOrEqual),
+                };
+                let result = match (lt, rt) {
+                    (Ty::Int, Ty::Int) => self.b.ins().icmp(int_cc, l, r),
+                    (Ty::Float, Ty::Float) => self.b.ins().fcmp(float_cc, l, r),
+                    _ => return Err(Reject::Unsupported),
+                };
+                Ok((result, Ty::Bool))
+            }
+            TokenKind::EQUAL_EQUAL | TokenKind::BANG_EQUAL => {
+                let eq = op.kind == TokenKind::EQUAL_EQUAL;
+                let result = match (lt, rt) {
+                    (Ty::Int, Ty::Int) | (Ty::Bool, Ty::Bool) => {
+                        self.b.ins().icmp(if eq { IntCC::Equal } else { IntCC::NotEqual }, l, r)
+                    }
+                    (Ty::Float, Ty::Float) => {
+                        self.b.ins().fcmp(if eq { FloatCC::Equal } else { FloatCC::NotEqual }, l, r)
+                    }
+                    // Values of different types are never equal
+                    _ => self.b.ins().iconst(types::I8, !eq as i64),
+                };
+                Ok((result, Ty::Bool))
+            }
+            _ => Err(Reject::Unsupported),
+        }
+    }
+
+    fn arithmetic(&mut self, op: &TokenKind, (l, lt): (Reg, Ty), (r, rt): (Reg, Ty)) -> Lowered<(Reg, Ty)> {
+        match (lt, rt) {
+            // Anything past i64 bails out to the interpreter's i128
+            (Ty::Int, Ty::Int) => {
+                let result = match op {
+                    TokenKind::PLUS => self.checked(|ins| ins.sadd_overflow(l, r)),
+                    TokenKind::MINUS => self.checked(|ins| ins.ssub_overflow(l, r)),
+                    TokenKind::STAR => self.checked(|ins| ins.smul_overflow(l, r)),
+                    _ => {
+                        let zero = self.b.ins().icmp_imm(IntCC::Equal, r, 0);
+                        self.bail_if(zero);
+                        let min = self.b.ins().icmp_imm(IntCC::Equal, l, i64::MIN);
+                        let minus_one = self.b.ins().icmp_imm(IntCC::Equal, r, -1);
+                        let overflow = self.b.ins().band(min, minus_one);
+                        self.bail_if(overflow);
+
+                        if *op == TokenKind::SLASH {
+                            self.b.ins().sdiv(l, r)
+                        } else {
+                            self.b.ins().srem(l, r)
+                        }
+                    }
+                };
+                Ok((result, Ty::Int))
+            }
+            (Ty::Float, Ty::Float) | (Ty::Int, Ty::Float) | (Ty::Float, Ty::Int) => {
+                if *op == TokenKind::PERCENT {
+                    return Err(Reject::Unsupported);
+                }
+
+                // The interpreter only checks the Float operand for zero, on either side
+                if *op == TokenKind::SLASH {
+                    let float = if rt == Ty::Float { r } else { l };
+                    let zero = self.b.ins().f64const(0.0);
+                    let is_zero = self.b.ins().fcmp(FloatCC::Equal, float, zero);
+                    self.bail_if(is_zero);
+                }
+
+                let l = self.as_float(l, lt);
+                let r = self.as_float(r, rt);
+                let result = match op {
+                    TokenKind::PLUS => self.b.ins().fadd(l, r),
+                    TokenKind::MINUS => self.b.ins().fsub(l, r),
+                    TokenKind::STAR => self.b.ins().fmul(l, r),
+                    _ => self.b.ins().fdiv(l, r),
+                };
+                Ok((result, Ty::Float))
+            }
+            _ => Err(Reject::Unsupported),
+        }
+    }
+
+    fn logical(&mut self, left: &Expression, op: &Token, right: &Expression) -> Lowered<(Reg, Ty)> {
+        let (l, lt) = self.expr(left)?;
+        let truth = self.truthy(l, lt);
+        let (right_b, merge) = (self.b.create_block(), self.b.create_block());
+        self.b.append_block_param(merge, lt.clif());
+
+        if op.kind == TokenKind::OR {
+            self.b.ins().brif(truth, merge, &[l], right_b, &[]);
+        } else {
+            self.b.ins().brif(truth, right_b, &[], merge, &[l]);
+        }
+
+        self.b.switch_to_block(right_b);
+        let (r, rt) = self.expr(right)?;
+        // Either operand is the result, so they need the same type
+        if rt != lt {
+            return Err(Reject::Unsupported);
+        }
+        self.b.ins().jump(merge, &[r]);
+
+        self.b.switch_to_block(merge);
+        Ok((self.b.block_params(merge)[0], lt))
+    }
+
+    /// A direct recursive call, checked to return the type assumed for it
+    fn self_call(&mut self, callee: &Expression, args: &[Expression]) -> Lowered<(Reg, Ty)> {
+        let (Expression::Variable(name, None), Some(own)) = (callee, self.name) else {
+            return Err(Reject::Unsupported);
+        };
+        if name.lexeme != own.lexeme || args.len() != self.tys.len() {
+            return Err(Reject::Unsupported);
+        }
+
+        let tys = self.tys;
+        let mut values = Vec::new();
+        for (arg, &ty) in args.iter().zip(tys) {
+            let (value, arg_ty) = self.expr(arg)?;
+            if arg_ty != ty {
+                return Err(Reject::Unsupported);
+            }
+            values.push(value);
+        }
+
+        self.uses_self = true;
+        let call = self.b.ins().call(self.self_ref, &values);
+        let (status, payload) = (self.b.inst_results(call)[0], self.b.inst_results(call)[1]);
+        let mismatch = self.b.ins().icmp_imm(IntCC::NotEqual, status, self.assumed.status());
+        self.bail_if(mismatch);
+
+        let value = match self.assumed {
+            Ty::Int => payload,
+            Ty::Float => self.b.ins().bitcast(types::F64, MemFlags::new(), payload),
+            Ty::Bool => self.b.ins().ireduce(types::I8, payload),
+        };
+        Ok((value, self.assumed))
+    }
+
+    fn condition(&mut self, exp: &Expression) -> Lowered<Reg> {
+        let (value, ty) = self.expr(exp)?;
+        Ok(self.truthy(value, ty))
+    }
+
+    /// `Interpreter::to_bool`
+    fn truthy(&mut self, value: Reg, ty: Ty) -> Reg {
+        match ty {
+            Ty::Int => self.b.ins().icmp_imm(IntCC::NotEqual, value, 0),
+            Ty::Float => {
+                let zero = self.b.ins().f64const(0.0);
+                self.b.ins().fcmp(FloatCC::NotEqual, value, zero)
+            }
+            Ty::Bool => value,
+        }
+    }
+
+    fn as_float(&mut self, value: Reg, ty: Ty) -> Reg {
+        match ty {
+            Ty::Int => self.b.ins().fcvt_from_sint(types::F64, value),
+            _ => value,
+        }
+    }
+
+    /// An Int operation that bails out when it overflows
+    fn checked(&mut self, op: impl FnOnce(cranelift_frontend::FuncInstBuilder) -> (Reg, Reg)) -> Reg {
+        let (result, overflow) = op(self.b.ins());
+        self.bail_if(overflow);
+        result
+    }
+
+    fn bail_if(&mut self, cond: Reg) {
+        let next = self.b.create_block();
+        self.b.ins().brif(cond, self.bail, &[], next, &[]);
+        self.b.switch_to_block(next);
+    }
+
+    fn ret(&mut self, status: i64, value: Option<(Reg, Ty)>) {
+        let status = self.b.ins().iconst(types::I64, status);
+        let payload = match value {
+            None => self.b.ins().iconst(types::I64, 0),
+            Some((value, Ty::Int)) => value,
+            Some((value, Ty::Float)) => self.b.ins().bitcast(types::I64, MemFlags::new(), value),
+            Some((value, Ty::Bool)) => self.b.ins().uextend(types::I64, value),
+        };
+        self.b.ins().return_(&[status, payload]);
+    }
+
+    /// Code after a `return` or `break` goes in a block nothing jumps to
+    fn unreachable(&mut self) {
+        let block = self.b.create_block();
+        self.b.switch_to_block(block);
+    }
+
+    fn local(&self, name: &Token) -> Lowered<(Variable, Ty)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme).copied())
+            .ok_or(Reject::Unsupported)
+    }
+
+    fn new_var(&mut self, ty: Ty) -> Variable {
+        let var = Variable::from_u32(self.next_var);
+        self.next_var += 1;
+        self.b.declare_var(var, ty.clif());
+        var
+    }
+}
@@ -0,0 +1,63 @@
+//! The Dingle language as a library. An [`Engine`] runs files and snippets, calls their functions
+//! and reads or sets their globals:
+//!
+//! ```
+//! use dinglebob::{Engine, Value};
+//!
+//! let mut engine = Engine::new();
+//! engine.eval("define add(a, b) { return a + b; }").unwrap();
+//! assert_eq!(engine.call("add", vec![Value::Int(2), Value::Int(3)]), Ok(Value::Int(5)));
+//! ```
+
+// The interpreter's own style, kept as it started out: tokens and `Func` methods are named like
+// this, and functions return with an explicit `return`
+#![allow(non_snake_case, non_camel_case_types, clippy::upper_case_acronyms, clippy::needless_return)]
+// Errors carry a whole Diagnostic; they're rare, and only boxed where they travel as a BreakResult.
+// The AST boxes its lists to keep every node small.
+#![allow(clippy::result_large_err, clippy::box_collection)]
+
+mod scanner;
+mod token;
+mod ast;
+mod parser;
+mod interpreter;
+mod environment;
+mod func;
+mod math;
+mod random;
+mod fileio;
+mod system;
+mod stdlib;
+mod resolver;
+mod compiler;
+mod vm;
+
+#[cfg(feature = "jit")]
+mod jit;
+#[cfg(feature = "jit")]
+pub use jit::JitStats;
+
+pub mod project;
+pub mod plugin;
+pub mod explain;
+
+mod diagnostic;
+pub use diagnostic::{Diagnostic, ErrorFormat, Frame, Label, Phase, Severity, Span, JSON_VERSION};
+
+mod source;
+pub use source::{SourceId, SourceMap};
+
+mod value;
+pub use value::{Object, Value};
+
+mod host;
+pub use host::HostObject;
+
+mod stream;
+pub use stream::OutputBuffer;
+
+mod native;
+pub use native::{FromValue, IntoValue, NativeFn};
+
+mod engine;
+pub use engine::Engine;
@@ -1,121 +1,59 @@
-#![allow(warnings)]
-
 use std::io::{self, Write};
 use std::env;
-use std::fs;
-
-mod scanner; 
-use scanner::scan;    
-
-mod token; 
-use token::TokenKind; 
-
-mod ast; 
-use ast::Expression; 
-
-mod parser; 
-use parser::Parser; 
-
-mod interpreter; 
-use interpreter::Interpreter; 
-
-mod environment;
-
-mod func;
-
-mod resolver;
-use resolver::Resolver;
-
-fn run_source(source: &str, file: String ) {
-    let mut resolver = Resolver::new(false);
-    let mut interpreter = Interpreter::new(true, resolver.give_local(), false);
-
-    let token_result = scan(source, false, file, false);
-
-    if let Err(msg) = token_result{
-        //eprintln!("{}", msg);
-        return (); 
-    }
-
-    let mut parser = Parser::new(token_result.unwrap(), false);
-    let parsed_result = parser.parse();
-
-    if let Err(msg) = parsed_result{
-        //eprintln!("{}", msg);
-        return (); 
-    }
-
-    let resolver_result = resolver.resolve((&parsed_result).clone().unwrap());
-
-    if let Err(msg) = resolver_result{
-        //eprintln!("{}", msg);
-        return (); 
-    }
-
-    let interpreter_result = interpreter.prime_interpret(parsed_result.unwrap());
-
-    if let Err(msg) = interpreter_result{
-        //eprintln!("{}", msg);    
-        return (); 
-    }
+use std::path::PathBuf;
+
+use dinglebob::{explain, project, Engine, ErrorFormat};
+
+/// Command-line options that shape the engine
+struct Options {
+    seed: Option<u64>,
+    search_path: Vec<PathBuf>,
+    tree_walker: bool,
+    plugins: Vec<String>,
+    max_errors: Option<usize>,
+    error_format: ErrorFormat,
 }
 
-fn run_line(source: &str, debug: bool, interpreter: &mut Interpreter, resolver: &mut Resolver){
-
-    let mut resolver_save = resolver.clone();
-    let mut intepreter_save = interpreter.clone(resolver_save.give_local());
-
-    let token_result = scan(source, debug, String::from(""), true);
-
-    if let Err(msg) = token_result{
-        eprintln!("{}\n", msg);
-        return (); 
-    }
-
-    let mut parser = Parser::new(token_result.unwrap(), true);
-    let parsed_result = parser.parse();
-
-    if let Err(msg) = parsed_result{
-        eprintln!("{}\n", msg);
-        return (); 
-    }
-
-    let resolver_result = resolver.resolve((&parsed_result).clone().unwrap());
-
-    if let Err(msg) = resolver_result{
-        eprintln!("{}\n", msg);
-        *resolver = resolver_save;
-        return (); 
-    }
-
-    let interpreter_result = interpreter.prime_interpret(parsed_result.unwrap());
-
-    if let Err(msg) = interpreter_result{
-        eprintln!("{}\n", msg);
-        *resolver = resolver_save;
-        *interpreter = intepreter_save;
-
-        return (); 
+impl Options {
+    /// Exits with status 1 if a plugin can't be loaded
+    fn engine(&self, mut engine: Engine) -> Engine {
+        if let Some(n) = self.seed {
+            engine.seed(n);
+        }
+        for dir in &self.search_path {
+            engine.add_search_path(dir.clone());
+        }
+        engine.set_tree_walker(self.tree_walker);
+        if let Some(n) = self.max_errors {
+            engine.set_max_errors(n);
+        }
+        engine.set_error_format(self.error_format);
+        for path in &self.plugins {
+            // The engine has already reported the error
+            if engine.load_plugin(path).is_err() {
+                std::process::exit(1);
+            }
+        }
+        engine
     }
 }
 
-fn run_file(path: &str) {
-    let contents = fs::read_to_string(path)
-        .unwrap_or_else(|_| {
-            eprintln!("Could not read file '{}'", path);
-            std::process::exit(1);
-        });
+/// Runs a whole file and returns the process exit status
+fn run_file(mut engine: Engine, path: &str, script_args: Vec<String>) -> i32 {
+    engine.set_script_args(script_args);
 
-    run_source(&contents, String::from(path));
+    // The engine has already reported the errors; the status is how many there were
+    match engine.run_file(path) {
+        Ok(code) => code,
+        Err(diagnostic) => diagnostic.exit_status(),
+    }
 }
 
-fn repl() -> io::Result<()> {
+/// Returns the exit status: the code passed to `exit`, or 0
+fn repl(mut engine: Engine) -> io::Result<i32> {
     println!("Dinglebob Interpreter");
     println!("Type 'exit' to quit.\n");
 
-    let mut resolver = Resolver::new(true);
-    let mut interpreter = Interpreter::new(true, resolver.give_local(), true);
-
     loop {
         let mut input = String::new();
         print!(">>> ");
@@ -129,24 +67,147 @@ fn repl() -> io::Result<()> {
         if trimmed == "exit" {
             break;
         }
-        run_line(&input, false, &mut interpreter, &mut resolver);
+        // The engine has already reported any error
+        let _ = engine.eval(&input);
+        if let Some(code) = engine.exit_status() {
+            return Ok(code);
+        }
     }
 
-    Ok(())
+    Ok(0)
 }
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    
-    match args.len() {
-        1 => repl(),
-        2 => {
-            run_file(&args[1]);
-            Ok(())
+/// `dinglebob run`: runs the entry point of the project around the working directory
+fn run_project(mut engine: Engine, script_args: Vec<String>) -> i32 {
+    // The engine has already reported the error
+    match engine.load_project() {
+        Ok(entry) => run_file(engine, &entry, script_args),
+        Err(diagnostic) => diagnostic.exit_status(),
+    }
+}
+
+/// `dinglebob new <name>`; `engine` only reports the error, if there is one
+fn new_project(engine: Engine, name: &str) -> i32 {
+    match project::create(name) {
+        Ok(dir) => {
+            println!("Created project '{}' in {}", name, dir.display());
+            0
         }
-        _ => {
-            eprintln!("Usage:\n  dinglebob\n  dinglebob <file>");
-            std::process::exit(1);
+        Err(diagnostic) => {
+            engine.report_error(&diagnostic);
+            diagnostic.exit_status()
         }
     }
 }
+
+/// `dinglebob --explain <code>`
+fn explain_code(code: &str) -> i32 {
+    match explain::explanation(&code.to_uppercase()) {
+        Some(text) => {
+            print!("{}", text);
+            0
+        }
+        None => {
+            eprintln!("'{}' is not an error code; codes look like D0201, as shown in error reports.", code);
+            1
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage:\n  dinglebob [options]\n  dinglebob [options] <file> [args...]\n  dinglebob [options] run [args...]\n  dinglebob new <name>\n  dinglebob --explain <code>\n\nOptions:\n  --seed <n>       seed the random module\n  --lib-dir <dir>  also search <dir> for imports (repeatable)\n  --tree-walker    run on the tree-walking interpreter instead of the bytecode VM\n  --plugin <path>  load a native plugin library before running (repeatable)\n  --max-errors <n> stop scanning or parsing a file after <n> errors (default 20)\n  --error-format=<human|json>\n                   write errors as reports quoting the source (default), or as JSON lines");
+    std::process::exit(1);
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+
+    // Flags come before the file; everything after the file belongs to the script
+    let mut seed = None;
+    let mut lib_dirs = Vec::new();
+    let mut tree_walker = false;
+    let mut plugins = Vec::new();
+    let mut max_errors = None;
+    let mut error_format = ErrorFormat::Human;
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // `--seed <n>` makes the `random` module replay the same sequence
+            "--seed" => {
+                let Some(n) = args.next().and_then(|n| n.parse::<u64>().ok()) else {
+                    eprintln!("'--seed' expects an integer.");
+                    usage();
+                };
+                seed = Some(n);
+            }
+            "--lib-dir" => {
+                let Some(dir) = args.next() else {
+                    eprintln!("'--lib-dir' expects a directory.");
+                    usage();
+                };
+                lib_dirs.push(PathBuf::from(dir));
+            }
+            "--plugin" => {
+                let Some(path) = args.next() else {
+                    eprintln!("'--plugin' expects a path.");
+                    usage();
+                };
+                plugins.push(path);
+            }
+            "--max-errors" => {
+                let Some(n) = args.next().and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0) else {
+                    eprintln!("'--max-errors' expects a positive integer.");
+                    usage();
+                };
+                max_errors = Some(n);
+            }
+            // Prints the explanation and exits, ignoring anything else on the line
+            "--explain" => {
+                let Some(code) = args.next() else {
+                    eprintln!("'--explain' expects an error code.");
+                    usage();
+                };
+                std::process::exit(explain_code(&code));
+            }
+            "--error-format=human" => error_format = ErrorFormat::Human,
+            "--error-format=json" => error_format = ErrorFormat::Json,
+            // For comparing the two engines; the REPL always uses the tree-walker
+            "--tree-walker" => tree_walker = true,
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown option '{}'.", flag);
+                usage();
+            }
+            _ => {
+                file = Some(arg);
+                break;
+            }
+        }
+    }
+
+    // Imports not found next to the importing file are looked up here, in order, before the standard library
+    let mut search_path = lib_dirs;
+    if let Some(paths) = env::var_os("DINGLE_PATH") {
+        search_path.extend(env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+
+    let options = Options { seed, search_path, tree_walker, plugins, max_errors, error_format };
+
+    // `run` and `new` are commands, anything else is a file to run
+    let code = match file.as_deref() {
+        // The REPL always uses the tree-walker
+        None => repl(Options { tree_walker: true, ..options }.engine(Engine::reporting()))?,
+        Some("run") => run_project(options.engine(Engine::reporting()), args.collect()),
+        Some("new") => {
+            let (Some(name), None) = (args.next(), args.next()) else {
+                usage();
+            };
+            let mut engine = Engine::reporting();
+            engine.set_error_format(options.error_format);
+            new_project(engine, &name)
+        }
+        Some(path) => run_file(options.engine(Engine::reporting()), path, args.collect()),
+    };
+
+    io::stdout().flush()?;
+    std::process::exit(code);
+}
@@ -0,0 +1,478 @@
+use crate::interpreter::Interpreter;
+use crate::environment::Environment;
+use crate::ast::Value;
+use crate::ast::BreakResult;
+use crate::func::Func;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Builds the `math` module value; its functions close over `closure` like every other built-in
+pub fn module(closure: &Rc<RefCell<Environment>>) -> Value {
+    let members = Rc::new(RefCell::new(Environment::new(None)));
+
+    {
+        let mut edittable_env = members.borrow_mut();
+
+        let mut define = |name: &str, func: Box<dyn Func>| {
+            edittable_env.define_default(
+                name,
+                Value::Call(Rc::from(func), Rc::clone(closure))
+            )
+        };
+
+        define("sqrt", Box::new(Sqrt {}));
+        define("pow", Box::new(Pow {}));
+        define("floor", Box::new(Rounding { name: "floor", op: f64::floor }));
+        define("ceil", Box::new(Rounding { name: "ceil", op: f64::ceil }));
+        define("round", Box::new(Rounding { name: "round", op: f64::round }));
+        define("trunc", Box::new(Rounding { name: "trunc", op: f64::trunc }));
+        define("sin", Box::new(FloatFn { name: "sin", op: f64::sin }));
+        define("cos", Box::new(FloatFn { name: "cos", op: f64::cos }));
+        define("tan", Box::new(FloatFn { name: "tan", op: f64::tan }));
+        define("asin", Box::new(UnitDomainFn { name: "asin", op: f64::asin }));
+        define("acos", Box::new(UnitDomainFn { name: "acos", op: f64::acos }));
+        define("atan", Box::new(FloatFn { name: "atan", op: f64::atan }));
+        define("atan2", Box::new(Atan2 {}));
+        define("exp", Box::new(FloatFn { name: "exp", op: f64::exp }));
+        define("log", Box::new(Log {}));
+        define("min", Box::new(Extreme { name: "min", want_greater: false }));
+        define("max", Box::new(Extreme { name: "max", want_greater: true }));
+        define("gcd", Box::new(Gcd {}));
+        define("lcm", Box::new(Lcm {}));
+        define("clamp", Box::new(Clamp {}));
+        define("is_nan", Box::new(IsNan {}));
+        define("is_inf", Box::new(IsInf {}));
+
+        edittable_env.define_default("PI", Value::Float(std::f64::consts::PI));
+        edittable_env.define_default("E", Value::Float(std::f64::consts::E));
+        edittable_env.define_default("INF", Value::Float(f64::INFINITY));
+        edittable_env.define_default("NAN", Value::Float(f64::NAN));
+    }
+
+    Value::Module(String::from("math"), members)
+}
+
+fn arity_error(name: &str, expected: &str, got: usize) -> BreakResult {
+    BreakResult::error_code("D0405", format!(
+        "Arity error: 'math.{}' takes {}, but got {}.",
+        name, expected, got
+    ))
+}
+
+fn domain_error(name: &str, msg: String) -> BreakResult {
+    BreakResult::error(format!("Domain error: 'math.{}' {}", name, msg))
+}
+
+/// Converts an integral Float back to an Int, rejecting NaN, infinities and overflow
+fn float_to_int(name: &str, n: f64) -> Result<Value, BreakResult> {
+    if !n.is_finite() || n < i128::MIN as f64 || n >= i128::MAX as f64 {
+        return Err(domain_error(name, format!("cannot convert {} to an Int.", n)));
+    }
+
+    Ok(Value::Int(n as i128))
+}
+
+/// Unsigned, since gcd(i128::MIN, 0) is one past the largest Int
+fn gcd(a: i128, b: i128) -> u128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Reads an Int or Float argument as f64
+fn as_f64(func: &dyn Func, v: &Value) -> Result<f64, BreakResult> {
+    let Value::Float(n) = func.expect(v.clone(), "Float")? else {unreachable!()};
+    Ok(n)
+}
+
+pub struct Sqrt;
+
+impl Func for Sqrt {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.sqrt")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("sqrt", "1 argument", input_args.len()));
+        }
+
+        // Perfect squares stay Int
+        if let Value::Int(n) = input_args[0] {
+            if n < 0 {
+                return Err(domain_error("sqrt", format!("is undefined for negative numbers, got {}.", n)));
+            }
+
+            let root = (n as f64).sqrt() as i128;
+            for candidate in [root - 1, root, root + 1] {
+                if candidate >= 0 && candidate.checked_mul(candidate) == Some(n) {
+                    return Ok(Value::Int(candidate));
+                }
+            }
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        if n < 0.0 {
+            return Err(domain_error("sqrt", format!("is undefined for negative numbers, got {}.", n)));
+        }
+
+        Ok(Value::Float(n.sqrt()))
+    }
+}
+
+pub struct Pow;
+
+impl Func for Pow {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.pow")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("pow", "2 arguments (base, exponent)", input_args.len()));
+        }
+
+        // Int ** non-negative Int is exact; anything else goes through Float
+        if let (Value::Int(base), Value::Int(exp)) = (&input_args[0], &input_args[1]) {
+            if *exp >= 0 {
+                let result = u32::try_from(*exp).ok().and_then(|e| base.checked_pow(e));
+                return match result {
+                    Some(n) => Ok(Value::Int(n)),
+                    None => Err(domain_error("pow", format!("overflowed computing {} ** {}.", base, exp))),
+                };
+            }
+
+            if *base == 0 {
+                return Err(domain_error("pow", "is undefined for 0 to a negative power.".to_string()));
+            }
+        }
+
+        let base = as_f64(self, &input_args[0])?;
+        let exp = as_f64(self, &input_args[1])?;
+        let result = base.powf(exp);
+
+        if result.is_nan() && !base.is_nan() && !exp.is_nan() {
+            return Err(domain_error("pow", format!("is undefined for {} ** {}.", base, exp)));
+        }
+
+        Ok(Value::Float(result))
+    }
+}
+
+/// `floor`, `ceil`, `round` and `trunc`: always return an Int
+pub struct Rounding {
+    name: &'static str,
+    op: fn(f64) -> f64,
+}
+
+impl Func for Rounding {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return format!("math.{}", self.name)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error(self.name, "1 argument", input_args.len()));
+        }
+
+        if let Value::Int(n) = input_args[0] {
+            return Ok(Value::Int(n));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        float_to_int(self.name, (self.op)(n))
+    }
+}
+
+/// Float functions defined for every input, e.g. `sin` and `exp`
+pub struct FloatFn {
+    name: &'static str,
+    op: fn(f64) -> f64,
+}
+
+impl Func for FloatFn {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return format!("math.{}", self.name)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error(self.name, "1 argument", input_args.len()));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        Ok(Value::Float((self.op)(n)))
+    }
+}
+
+/// `asin` and `acos`: only defined on [-1, 1]
+pub struct UnitDomainFn {
+    name: &'static str,
+    op: fn(f64) -> f64,
+}
+
+impl Func for UnitDomainFn {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return format!("math.{}", self.name)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error(self.name, "1 argument", input_args.len()));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        if !(-1.0..=1.0).contains(&n) {
+            return Err(domain_error(self.name, format!("is only defined on [-1, 1], got {}.", n)));
+        }
+
+        Ok(Value::Float((self.op)(n)))
+    }
+}
+
+pub struct Atan2;
+
+impl Func for Atan2 {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.atan2")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("atan2", "2 arguments (y, x)", input_args.len()));
+        }
+
+        let y = as_f64(self, &input_args[0])?;
+        let x = as_f64(self, &input_args[1])?;
+        Ok(Value::Float(y.atan2(x)))
+    }
+}
+
+pub struct Log;
+
+impl Func for Log {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.log")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 && input_args.len() != 2 {
+            return Err(arity_error("log", "1 or 2 arguments (x, base)", input_args.len()));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        if n <= 0.0 {
+            return Err(domain_error("log", format!("is only defined for positive numbers, got {}.", n)));
+        }
+
+        // Natural log unless a base is given
+        let Some(base) = input_args.get(1) else {
+            return Ok(Value::Float(n.ln()));
+        };
+
+        let base = as_f64(self, base)?;
+        if base <= 0.0 || base == 1.0 {
+            return Err(domain_error("log", format!("base must be positive and not 1, got {}.", base)));
+        }
+
+        Ok(Value::Float(n.log(base)))
+    }
+}
+
+/// `min` and `max` over their arguments; the winner keeps its own type
+pub struct Extreme {
+    name: &'static str,
+    want_greater: bool,
+}
+
+impl Func for Extreme {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return format!("math.{}", self.name)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.is_empty() {
+            return Err(arity_error(self.name, "at least 1 argument", 0));
+        }
+
+        let mut best = input_args[0].clone();
+        let mut best_n = as_f64(self, &best)?;
+
+        for arg in input_args.iter().skip(1) {
+            let n = as_f64(self, arg)?;
+            if (self.want_greater && n > best_n) || (!self.want_greater && n < best_n) {
+                best = arg.clone();
+                best_n = n;
+            }
+        }
+
+        Ok(best)
+    }
+}
+
+pub struct Gcd;
+
+impl Func for Gcd {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.gcd")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("gcd", "2 arguments (Int, Int)", input_args.len()));
+        }
+
+        let Value::Int(a) = self.expect(input_args[0].clone(), "Int")? else {unreachable!()};
+        let Value::Int(b) = self.expect(input_args[1].clone(), "Int")? else {unreachable!()};
+
+        match i128::try_from(gcd(a, b)) {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => Err(domain_error("gcd", format!("of {} and {} is too large for an Int.", a, b))),
+        }
+    }
+}
+
+pub struct Lcm;
+
+impl Func for Lcm {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.lcm")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("lcm", "2 arguments (Int, Int)", input_args.len()));
+        }
+
+        let Value::Int(a) = self.expect(input_args[0].clone(), "Int")? else {unreachable!()};
+        let Value::Int(b) = self.expect(input_args[1].clone(), "Int")? else {unreachable!()};
+
+        if a == 0 || b == 0 {
+            return Ok(Value::Int(0));
+        }
+
+        let lcm = (a.unsigned_abs() / gcd(a, b)).checked_mul(b.unsigned_abs());
+        match lcm.and_then(|n| i128::try_from(n).ok()) {
+            Some(n) => Ok(Value::Int(n)),
+            None => Err(domain_error("lcm", format!("overflowed computing lcm({}, {}).", a, b))),
+        }
+    }
+}
+
+pub struct Clamp;
+
+impl Func for Clamp {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.clamp")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 3 {
+            return Err(arity_error("clamp", "3 arguments (x, low, high)", input_args.len()));
+        }
+
+        let x = as_f64(self, &input_args[0])?;
+        let low = as_f64(self, &input_args[1])?;
+        let high = as_f64(self, &input_args[2])?;
+
+        if low > high {
+            return Err(domain_error("clamp", format!("low bound {} is greater than high bound {}.", low, high)));
+        }
+
+        if x < low {
+            return Ok(input_args[1].clone());
+        }
+        if x > high {
+            return Ok(input_args[2].clone());
+        }
+        Ok(input_args[0].clone())
+    }
+}
+
+pub struct IsNan;
+
+impl Func for IsNan {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.is_nan")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("is_nan", "1 argument", input_args.len()));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        Ok(Value::Bool(n.is_nan()))
+    }
+}
+
+pub struct IsInf;
+
+impl Func for IsInf {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("math.is_inf")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("is_inf", "1 argument", input_args.len()));
+        }
+
+        let n = as_f64(self, &input_args[0])?;
+        Ok(Value::Bool(n.is_infinite()))
+    }
+}
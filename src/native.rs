@@ -0,0 +1,345 @@
+use crate::ast::{self, BreakResult};
+use crate::func::Func;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use std::fmt;
+
+/// A Rust type a native function can take as an argument
+pub trait FromValue: Sized {
+    /// How type errors name what the argument expects, e.g. "Int"
+    fn type_name() -> String;
+    fn from_value(value: &Value) -> Option<Self>;
+
+    /// Whether a value `from_value` rejected has the right Dingle type but doesn't fit, like an
+    /// Int too large for an `i64`; such an argument is a value error rather than a type error
+    fn out_of_range(_value: &Value) -> bool {
+        false
+    }
+}
+
+/// A Rust type a native function can return
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// A Rust closure that can be registered as a built-in: any `Fn` of up to six `FromValue`
+/// arguments returning `Result<impl IntoValue, impl Display>`. The error becomes a runtime error.
+pub trait NativeFn<Args>: 'static {
+    fn arity(&self) -> usize;
+    fn invoke(&self, name: &str, args: &[Value]) -> Result<Value, String>;
+}
+
+/// Converts the argument at `index`, or describes why it doesn't fit
+fn argument<T: FromValue>(name: &str, index: usize, value: &Value) -> Result<T, String> {
+    T::from_value(value).ok_or_else(|| {
+        if T::out_of_range(value) {
+            return format!(
+                "Value error: argument {} to '{}' is out of range for a {}, got {}.",
+                index + 1,
+                name,
+                T::type_name(),
+                value
+            );
+        }
+        format!(
+            "Type error: '{}' expects {} for argument {}, but got {}.",
+            name,
+            T::type_name(),
+            index + 1,
+            value.type_name()
+        )
+    })
+}
+
+macro_rules! native_fn {
+    ($($arg:ident: $ty:ident),*) => {
+        impl<F, R, E, $($ty),*> NativeFn<($($ty,)*)> for F
+        where
+            F: Fn($($ty),*) -> Result<R, E> + 'static,
+            R: IntoValue,
+            E: fmt::Display,
+            $($ty: FromValue,)*
+        {
+            fn arity(&self) -> usize {
+                let names: &[&str] = &[$(stringify!($arg)),*];
+                names.len()
+            }
+
+            // The closure without parameters leaves the name and arguments unused
+            #[allow(unused_mut, unused_variables)]
+            fn invoke(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+                // `Native::call` already checked there's one value per parameter
+                let mut args = args.iter().enumerate();
+                $(
+                    let (index, value) = args.next().unwrap();
+                    let $arg = argument::<$ty>(name, index, value)?;
+                )*
+                self($($arg),*).map(IntoValue::into_value).map_err(|e| e.to_string())
+            }
+        }
+    };
+}
+
+native_fn!();
+native_fn!(a: A1);
+native_fn!(a: A1, b: A2);
+native_fn!(a: A1, b: A2, c: A3);
+native_fn!(a: A1, b: A2, c: A3, d: A4);
+native_fn!(a: A1, b: A2, c: A3, d: A4, e: A5);
+native_fn!(a: A1, b: A2, c: A3, d: A4, e: A5, f: A6);
+
+/// A native function's body: called with its name, for error messages, and its arguments
+type NativeBody = Box<dyn Fn(&str, &[Value]) -> Result<Value, String>>;
+
+/// A registered closure as a Dingle function value
+pub struct Native {
+    name: String,
+    arity: usize,
+    body: NativeBody,
+}
+
+impl Native {
+    pub fn new<Args>(name: &str, f: impl NativeFn<Args>) -> Self {
+        Native {
+            name: String::from(name),
+            arity: f.arity(),
+            body: Box::new(move |name, args| f.invoke(name, args)),
+        }
+    }
+}
+
+impl Func for Native {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        self.name.clone()
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.arity)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<ast::Value>) -> Result<ast::Value, BreakResult> {
+        if input_args.len() != self.arity {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: '{}' takes {} argument(s), but got {}.",
+                self.name,
+                self.arity,
+                input_args.len()
+            )));
+        }
+
+        let args: Vec<Value> = input_args.iter().map(Value::from_internal).collect();
+        match (self.body)(&self.name, &args) {
+            Ok(value) => Ok(value.into_internal()),
+            Err(msg) => Err(BreakResult::error(msg)),
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn type_name() -> String {
+        String::from("any value")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for i128 {
+    fn type_name() -> String {
+        String::from("Int")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn type_name() -> String {
+        String::from("64-bit Int")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(n) => i64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    fn out_of_range(value: &Value) -> bool {
+        matches!(value, Value::Int(_))
+    }
+}
+
+impl FromValue for usize {
+    fn type_name() -> String {
+        String::from("non-negative Int")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(n) => usize::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    fn out_of_range(value: &Value) -> bool {
+        matches!(value, Value::Int(_))
+    }
+}
+
+impl FromValue for f64 {
+    /// Ints are accepted too, like the built-ins' `Float` parameters
+    fn type_name() -> String {
+        String::from("Float")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(n) => Some(*n as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn type_name() -> String {
+        String::from("Bool")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn type_name() -> String {
+        String::from("String")
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn type_name() -> String {
+        format!("List of {}", T::type_name())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(items) => items.iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+
+    /// Every item has the right type, and some don't fit
+    fn out_of_range(value: &Value) -> bool {
+        match value {
+            Value::List(items) => items.iter().all(|item| T::from_value(item).is_some() || T::out_of_range(item)),
+            _ => false,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn type_name() -> String {
+        format!("{} or None", T::type_name())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::None => Some(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+
+    fn out_of_range(value: &Value) -> bool {
+        T::out_of_range(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::None
+    }
+}
+
+impl IntoValue for i128 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self as i128)
+    }
+}
+
+impl IntoValue for usize {
+    fn into_value(self) -> Value {
+        Value::Int(self as i128)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(String::from(self))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => value.into_value(),
+            None => Value::None,
+        }
+    }
+}
@@ -1,60 +1,154 @@
-use crate::func::Func;
 use crate::token::TokenKind;
 use crate::token::Token;
+use crate::diagnostic::{Diagnostic, Phase, Span};
 use crate::ast::Expression;
 use crate::ast::Value;
 use crate::ast::Statement;
-use std::env::args;
-use std::fs;
-use std::ops::Index;
 use std::mem;
-use ariadne::{Color, ColorGenerator, Fmt, Label, Report, ReportKind, Source};
+use std::rc::Rc;
 
-type ParseResult<T> = Result<T, String>;
+type ParseResult<T> = Result<T, Diagnostic>;
 
 pub struct Parser {
     tokens_list: Vec<Token>,
     curr_index: usize,
-    var_id: i32,
-    repl: bool,
+    // Errors recovered from so far; parsing stops once there are max_errors of them
+    errors: Vec<Diagnostic>,
+    max_errors: usize,
 }
 
 impl Parser {
-    pub fn new(tokens_list: Vec<Token>, repl: bool) -> Self {
+    pub fn new(tokens_list: Vec<Token>, max_errors: usize) -> Self {
         Parser {
             tokens_list,
             curr_index: 0,
-            var_id: 0,
-            repl,
+            errors: Vec::new(),
+            max_errors,
         }
     }
 
     fn atEnd(&self) -> bool {
-        (&self.tokens_list[self.curr_index]).kind == TokenKind::EOF
+        self.tokens_list[self.curr_index].kind == TokenKind::EOF
     }
 
     // ------------------------------------------------------------
     // Helper: "varDeclaration style" error call without copy/paste
     // ------------------------------------------------------------
-    fn err_from<T>(&self, start_error: usize, msg: &str) -> ParseResult<T> {
+    fn err_from<T>(&self, start_error: usize, code: &str, msg: &str) -> ParseResult<T> {
         let idx = self.curr_index.min(self.tokens_list.len().saturating_sub(1));
         let end_error = self.tokens_list[idx].id_end as usize;
-        self.handle_error(msg, start_error, end_error)
+        self.handle_error(code, msg, start_error, end_error)
     }
 
-    fn err_from_owned<T>(&self, start_error: usize, msg: String) -> ParseResult<T> {
-        self.err_from(start_error, msg.as_str())
+    fn err_from_owned<T>(&self, start_error: usize, code: &str, msg: String) -> ParseResult<T> {
+        self.err_from(start_error, code, msg.as_str())
     }
 
+    /// Parses the whole file. After an error it skips to the next statement and carries on, so
+    /// one run reports every error (up to `max_errors`), the first leading.
     pub fn parse(&mut self) -> ParseResult<Vec<Statement>> {
         let mut statement_list = Vec::new();
 
         while !self.atEnd() {
-            let dec = self.declaration()?;
-            statement_list.push(dec);
+            let start = self.curr_index;
+            match self.topDeclaration() {
+                Ok(dec) => statement_list.push(dec),
+                Err(e) => {
+                    // A block that hit the limit has recorded its error already
+                    if self.errors.len() < self.max_errors {
+                        self.errors.push(e);
+                    }
+                    if self.errors.len() >= self.max_errors {
+                        break;
+                    }
+                    self.synchronize(start, false);
+                }
+            }
+        }
+
+        if self.errors.len() >= self.max_errors && !self.atEnd() {
+            let note = format!("Stopped after {} error(s); fix these to see the rest.", self.max_errors);
+            let last = self.errors.last_mut().unwrap();
+            *last = last.clone().with_note(note);
+        }
+
+        match Diagnostic::collect(mem::take(&mut self.errors)) {
+            Some(errors) => Err(errors),
+            None => Ok(statement_list),
+        }
+    }
+
+    /// Skips ahead after an error in the statement at `start`, to where the next one likely starts:
+    /// past its ';' or its block, or at a statement keyword on a new line. Inside a block it also
+    /// stops at '}', so the block can still close.
+    fn synchronize(&mut self, start: usize, in_block: bool) {
+        // The ';'s in the header of a `for` or the like don't end it; its block does
+        let compound = matches!(
+            self.tokens_list[start].kind,
+            TokenKind::FOR | TokenKind::IF | TokenKind::WHILE | TokenKind::DEFINE
+        );
+
+        // Always get past a token that couldn't start anything
+        if self.curr_index == start && !self.atEnd() {
+            self.curr_index += 1;
+        }
+
+        while !self.atEnd() {
+            let token = &self.tokens_list[self.curr_index];
+            match token.kind {
+                TokenKind::LEFT_BRACE => {
+                    self.skip_group();
+                    if self.check(TokenKind::SEMICOLON) {
+                        self.curr_index += 1;
+                    }
+                    if !self.check(TokenKind::ELSE) {
+                        return;
+                    }
+                }
+                TokenKind::RIGHT_BRACE if in_block => return,
+                TokenKind::SEMICOLON => {
+                    self.curr_index += 1;
+                    if !compound {
+                        return;
+                    }
+                }
+                TokenKind::LET | TokenKind::DEFINE | TokenKind::IF | TokenKind::WHILE | TokenKind::FOR
+                | TokenKind::PRINT | TokenKind::RETURN | TokenKind::BREAK | TokenKind::FROM
+                | TokenKind::EXPORT | TokenKind::CLASS
+                    if token.line > self.tokens_list[self.curr_index - 1].line => return,
+                _ => self.curr_index += 1,
+            }
         }
+    }
 
-        Ok(statement_list)
+    /// Skips a '{ ... }' and everything nested in it
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        while !self.atEnd() {
+            match self.tokens_list[self.curr_index].kind {
+                TokenKind::LEFT_BRACE => depth += 1,
+                TokenKind::RIGHT_BRACE => depth -= 1,
+                _ => {}
+            }
+            self.curr_index += 1;
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    // Imports and exports describe the file itself, so they can't appear inside a block or function
+    fn topDeclaration(&mut self) -> ParseResult<Statement> {
+        let currentToken = &self.tokens_list[self.curr_index];
+        self.curr_index += 1;
+
+        match currentToken.kind {
+            TokenKind::EXPORT => return self.exportDeclaration(currentToken.clone()),
+            TokenKind::FROM => return self.fromImport(),
+            _ => { self.curr_index -= 1; }
+        }
+
+        self.declaration()
     }
 
     fn declaration(&mut self) -> ParseResult<Statement> {
@@ -66,17 +160,91 @@ impl Parser {
         match currentToken.kind {
             TokenKind::LET => return self.varDeclaration(),
             TokenKind::DEFINE => return self.function(),
+            TokenKind::EXPORT | TokenKind::FROM => {
+                return self.err_from_owned(
+                    start_error,
+                    "D0209",
+                    format!("'{}' is only allowed at the top level of a file.", currentToken.lexeme),
+                );
+            }
             _ => { self.curr_index -= 1; }
         }
 
         self.statement()
     }
 
+    fn exportDeclaration(&mut self, keyword: Token) -> ParseResult<Statement> {
+        let start_error = self.tokens_list[self.curr_index].id as usize;
+
+        if self.match_token(&[TokenKind::LET]) {
+            return Ok(Statement::Export(keyword, Box::new(self.varDeclaration()?)));
+        }
+
+        if self.match_token(&[TokenKind::DEFINE]) {
+            return Ok(Statement::Export(keyword, Box::new(self.function()?)));
+        }
+
+        self.err_from(start_error, "D0209", "Expected 'let' or 'define' after 'export'.")
+    }
+
+    // from "path.dingle" import name, other as alias;
+    fn fromImport(&mut self) -> ParseResult<Statement> {
+        let start_error = self.tokens_list[self.curr_index].id as usize;
+
+        if !self.check(TokenKind::STRING) {
+            return self.err_from(start_error, "D0209", "Expected a module path string after 'from'.");
+        }
+
+        let path = self.tokens_list[self.curr_index].clone();
+        self.curr_index += 1;
+
+        // `import` and `as` stay ordinary identifiers, so `import(...)` keeps working as a call
+        if !self.check_word("import") {
+            return self.err_from(start_error, "D0209", "Expected 'import' after the module path.");
+        }
+        self.curr_index += 1;
+
+        let mut names: Vec<(Token, Token)> = Vec::new();
+        loop {
+            if !self.check(TokenKind::IDENTIFIER) {
+                return self.err_from(start_error, "D0204", "Expected a name to import.");
+            }
+
+            let name = self.tokens_list[self.curr_index].clone();
+            self.curr_index += 1;
+
+            let mut alias = name.clone();
+            if self.check_word("as") {
+                self.curr_index += 1;
+
+                if !self.check(TokenKind::IDENTIFIER) {
+                    return self.err_from(start_error, "D0204", "Expected a name after 'as'.");
+                }
+
+                alias = self.tokens_list[self.curr_index].clone();
+                self.curr_index += 1;
+            }
+
+            names.push((name, alias));
+
+            if !self.match_token(&[TokenKind::COMMA]) {
+                break;
+            }
+        }
+
+        if !self.check(TokenKind::SEMICOLON) {
+            return self.err_from(start_error, "D0201", "Expected ';' after import list.");
+        }
+        self.curr_index += 1;
+
+        Ok(Statement::FromImport(path, names))
+    }
+
     fn varDeclaration(&mut self) -> ParseResult<Statement> {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
         if self.check(TokenKind::IDENTIFIER) {
-            let token: Token = (&self.tokens_list[self.curr_index]).clone();
+            let token: Token = self.tokens_list[self.curr_index].clone();
             self.curr_index += 1;
 
             if self.match_token(&[TokenKind::EQUAL]) {
@@ -87,7 +255,7 @@ impl Parser {
                     return Ok(Statement::Var(token, expr));
                 }
 
-                return self.err_from(start_error, "Expected ';' after variable declaration.");
+                return self.err_from(start_error, "D0201", "Expected ';' after variable declaration.");
             }
 
             if self.check(TokenKind::SEMICOLON) {
@@ -97,25 +265,26 @@ impl Parser {
 
             return self.err_from(
                 start_error,
+                "D0201",
                 "Expected '=' or ';' after variable name in variable declaration.",
             );
         }
 
-        self.err_from(start_error, "Expected an identifier after 'let' (variable name).")
+        self.err_from(start_error, "D0204", "Expected an identifier after 'let' (variable name).")
     }
 
     fn function(&mut self) -> ParseResult<Statement> {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
         if !self.check(TokenKind::IDENTIFIER) {
-            return self.err_from(start_error, "Expected an identifier after 'define' (function name).");
+            return self.err_from(start_error, "D0204", "Expected an identifier after 'define' (function name).");
         }
 
-        let name = (&self.tokens_list[self.curr_index]).clone();
+        let name = self.tokens_list[self.curr_index].clone();
         self.curr_index += 1;
 
         if !self.check(TokenKind::LEFT_PAREN) {
-            return self.err_from(start_error, "Expected '(' after function name in function declaration.");
+            return self.err_from(start_error, "D0203", "Expected '(' after function name in function declaration.");
         }
 
         let mut args_list: Vec<Token> = Vec::new();
@@ -124,10 +293,10 @@ impl Parser {
         if !self.check(TokenKind::RIGHT_PAREN) {
             loop {
                 if !self.check(TokenKind::IDENTIFIER) {
-                    return self.err_from(start_error, "Expected an identifier as a parameter name in function declaration.");
+                    return self.err_from(start_error, "D0204", "Expected an identifier as a parameter name in function declaration.");
                 }
 
-                args_list.push((&self.tokens_list[self.curr_index]).clone());
+                args_list.push(self.tokens_list[self.curr_index].clone());
                 self.curr_index += 1;
 
                 if !self.check(TokenKind::COMMA) {
@@ -139,22 +308,22 @@ impl Parser {
 
         if self.check(TokenKind::RIGHT_PAREN) {
             if args_list.len() > 255 {
-                return self.err_from(start_error, "Too many parameters: functions can have at most 255 parameters.");
+                return self.err_from(start_error, "D0208", "Too many parameters: functions can have at most 255 parameters.");
             }
             self.curr_index += 1;
         } else {
-            return self.err_from(start_error, "Expected ')' after parameter list.");
+            return self.err_from(start_error, "D0202", "Expected ')' after parameter list.");
         }
 
         if !self.check(TokenKind::LEFT_BRACE) {
-            return self.err_from(start_error, "Expected '{' to start function body.");
+            return self.err_from(start_error, "D0205", "Expected '{' to start function body.");
         }
 
         self.curr_index += 1;
 
         let Statement::Block(statements) = self.block()? else { unreachable!() };
 
-        Ok(Statement::Function(name, args_list, statements))
+        Ok(Statement::Function(name, args_list, Rc::from(*statements)))
     }
 
     fn statement(&mut self) -> ParseResult<Statement> {
@@ -172,7 +341,7 @@ impl Parser {
             TokenKind::BREAK => {
                 let statement = Statement::Break(currentToken.clone());
                 if !self.check(TokenKind::SEMICOLON) {
-                    return self.err_from(start_error, "Expected ';' after 'break'.");
+                    return self.err_from(start_error, "D0201", "Expected ';' after 'break'.");
                 }
                 self.curr_index += 1;
                 return Ok(statement);
@@ -188,7 +357,7 @@ impl Parser {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
         if !self.check(TokenKind::LEFT_PAREN) {
-            return self.err_from(start_error, "Expected '(' after 'for'.");
+            return self.err_from(start_error, "D0203", "Expected '(' after 'for'.");
         }
 
         self.curr_index += 1;
@@ -209,7 +378,7 @@ impl Parser {
             condition = Some(self.expression()?);
         }
         if !self.check(TokenKind::SEMICOLON) {
-            return self.err_from(start_error, "Expected ';' after loop condition in 'for' statement.");
+            return self.err_from(start_error, "D0201", "Expected ';' after loop condition in 'for' statement.");
         }
 
         self.curr_index += 1;
@@ -220,7 +389,7 @@ impl Parser {
         }
 
         if !self.check(TokenKind::RIGHT_PAREN) {
-            return self.err_from(start_error, "Expected ')' after for-clause list.");
+            return self.err_from(start_error, "D0202", "Expected ')' after for-clause list.");
         }
 
         self.curr_index += 1;
@@ -228,24 +397,18 @@ impl Parser {
         let mut body = self.statement()?;
 
         if !matches!(body, Statement::Block(_)) {
-            return self.err_from(start_error, "Expected a block '{ ... }' after 'for (...)'.");
+            return self.err_from(start_error, "D0205", "Expected a block '{ ... }' after 'for (...)'.");
         }
 
-        if increment.is_some() {
-            let mut s_array = Vec::new();
-            s_array.push(body);
-            s_array.push(Statement::Expression(increment.unwrap()));
-            body = Statement::Block(Box::new(s_array));
+        if let Some(increment) = increment {
+            body = Statement::Block(Box::new(vec![body, Statement::Expression(increment)]));
         }
 
         let cond = condition.unwrap_or(Expression::Literal(Value::Bool(true)));
         body = Statement::While(cond, Box::new(body));
 
-        if intializer.is_some() {
-            let mut s_array = Vec::new();
-            s_array.push(intializer.unwrap());
-            s_array.push(body);
-            body = Statement::Block(Box::new(s_array));
+        if let Some(intializer) = intializer {
+            body = Statement::Block(Box::new(vec![intializer, body]));
         }
 
         Ok(body)
@@ -258,7 +421,7 @@ impl Parser {
         let thenStatement = self.statement()?;
 
         if !matches!(thenStatement, Statement::Block(_)) {
-            return self.err_from(start_error, "Expected a block '{ ... }' after 'if' condition.");
+            return self.err_from(start_error, "D0205", "Expected a block '{ ... }' after 'if' condition.");
         }
 
         let mut elseStatement = Statement::Expression(Expression::Literal(Value::None));
@@ -268,7 +431,7 @@ impl Parser {
             elseStatement = self.statement()?;
 
             if !matches!(elseStatement, Statement::Block(_)) {
-                return self.err_from(start_error, "Expected a block '{ ... }' after 'else'.");
+                return self.err_from(start_error, "D0205", "Expected a block '{ ... }' after 'else'.");
             }
         }
 
@@ -283,7 +446,7 @@ impl Parser {
         if self.check(TokenKind::SEMICOLON) {
             self.curr_index += 1;
         } else {
-            return self.err_from(start_error, "Expected ';' after expression in 'print' statement.");
+            return self.err_from(start_error, "D0201", "Expected ';' after expression in 'print' statement.");
         }
 
         Ok(Statement::Print(expr))
@@ -292,7 +455,7 @@ impl Parser {
     fn returnStatement(&mut self) -> ParseResult<Statement> {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
-        let return_token = (&self.tokens_list[self.curr_index]).clone();
+        let return_token = self.tokens_list[self.curr_index].clone();
         let mut value = Expression::Literal(Value::None);
 
         if !self.check(TokenKind::SEMICOLON) {
@@ -300,7 +463,7 @@ impl Parser {
         }
 
         if !self.check(TokenKind::SEMICOLON) {
-            return self.err_from(start_error, "Expected ';' after return statement.");
+            return self.err_from(start_error, "D0201", "Expected ';' after return statement.");
         }
 
         self.curr_index += 1;
@@ -315,7 +478,7 @@ impl Parser {
         let statement = self.statement()?;
 
         if !matches!(statement, Statement::Block(_)) {
-            return self.err_from(start_error, "Expected a block '{ ... }' after 'while' condition.");
+            return self.err_from(start_error, "D0205", "Expected a block '{ ... }' after 'while' condition.");
         }
 
         Ok(Statement::While(expr, Box::new(statement)))
@@ -329,7 +492,7 @@ impl Parser {
         if self.check(TokenKind::SEMICOLON) {
             self.curr_index += 1;
         } else {
-            return self.err_from(start_error, "Expected ';' after expression.");
+            return self.err_from(start_error, "D0201", "Expected ';' after expression.");
         }
 
         Ok(Statement::Expression(expr))
@@ -342,7 +505,18 @@ impl Parser {
         let mut statement = Vec::new();
 
         while !self.check(TokenKind::RIGHT_BRACE) && !self.atEnd() {
-            statement.push(self.declaration()?);
+            let start = self.curr_index;
+            match self.declaration() {
+                Ok(dec) => statement.push(dec),
+                Err(e) => {
+                    self.errors.push(e.clone());
+                    // Give up on the whole file; `parse` won't record this one again
+                    if self.errors.len() >= self.max_errors {
+                        return Err(e);
+                    }
+                    self.synchronize(start, true);
+                }
+            }
         }
 
         if self.check(TokenKind::RIGHT_BRACE) {
@@ -350,7 +524,7 @@ impl Parser {
             return Ok(Statement::Block(Box::new(statement)));
         }
 
-        self.err_from(start_error, "Expected '}' to close block.")
+        self.err_from(start_error, "D0202", "Expected '}' to close block.")
     }
 
     fn expression(&mut self) -> ParseResult<Expression> {
@@ -361,7 +535,7 @@ impl Parser {
         let expr = self.or()?;
 
         if self.check(TokenKind::EQUAL) {
-            let equal_store = (&self.tokens_list[self.curr_index]).clone();
+            let equal_store = self.tokens_list[self.curr_index].clone();
             self.curr_index += 1;
             let value = self.expression()?;
             return Ok(Expression::Assign(Box::new(expr), equal_store, Box::new(value)));
@@ -374,7 +548,7 @@ impl Parser {
         let mut expr = self.and()?;
 
         while self.check(TokenKind::OR) {
-            let op = (&self.tokens_list[self.curr_index]).clone();
+            let op = self.tokens_list[self.curr_index].clone();
             self.curr_index += 1;
             let right = self.equality()?;
             expr = Expression::Logical(Box::new(expr), op, Box::new(right));
@@ -387,7 +561,7 @@ impl Parser {
         let mut expr = self.equality()?;
 
         while self.check(TokenKind::AND) {
-            let op = (&self.tokens_list[self.curr_index]).clone();
+            let op = self.tokens_list[self.curr_index].clone();
             self.curr_index += 1;
             let right = self.equality()?;
             expr = Expression::Logical(Box::new(expr), op, Box::new(right));
@@ -401,7 +575,7 @@ impl Parser {
         let type_list = [TokenKind::BANG_EQUAL, TokenKind::EQUAL_EQUAL];
 
         while self.match_token(&type_list) {
-            let operator: Token = (&self.tokens_list[self.curr_index - 1]).clone();
+            let operator: Token = self.tokens_list[self.curr_index - 1].clone();
             let right: Expression = self.comparison()?;
             expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -414,7 +588,7 @@ impl Parser {
         let type_list = [TokenKind::GREATER, TokenKind::GREATER_EQUAL, TokenKind::LESS, TokenKind::LESS_EQUAL];
 
         while self.match_token(&type_list) {
-            let operator: Token = (&self.tokens_list[self.curr_index - 1]).clone();
+            let operator: Token = self.tokens_list[self.curr_index - 1].clone();
             let right: Expression = self.term()?;
             expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -427,7 +601,7 @@ impl Parser {
         let type_list = [TokenKind::MINUS, TokenKind::PLUS];
 
         while self.match_token(&type_list) {
-            let operator: Token = (&self.tokens_list[self.curr_index - 1]).clone();
+            let operator: Token = self.tokens_list[self.curr_index - 1].clone();
             let right: Expression = self.factor()?;
             expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -440,7 +614,7 @@ impl Parser {
         let type_list = [TokenKind::SLASH, TokenKind::STAR, TokenKind::PERCENT];
 
         while self.match_token(&type_list) {
-            let operator: Token = (&self.tokens_list[self.curr_index - 1]).clone();
+            let operator: Token = self.tokens_list[self.curr_index - 1].clone();
             let right: Expression = self.unary()?;
             expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -451,8 +625,8 @@ impl Parser {
     fn unary(&mut self) -> ParseResult<Expression> {
         let type_list = [TokenKind::BANG, TokenKind::MINUS];
 
-        while self.match_token(&type_list) {
-            let operator: Token = (&self.tokens_list[self.curr_index - 1]).clone();
+        if self.match_token(&type_list) {
+            let operator: Token = self.tokens_list[self.curr_index - 1].clone();
             let right: Expression = self.unary()?;
             return Ok(Expression::Unary(operator, Box::new(right)));
         }
@@ -466,6 +640,18 @@ impl Parser {
         let mut expr = self.index()?;
 
         loop {
+            if self.check(TokenKind::DOT) {
+                self.curr_index += 1;
+
+                if !self.check(TokenKind::IDENTIFIER) {
+                    return self.err_from(start_error, "D0204", "Expected a member name after '.'.");
+                }
+
+                expr = Expression::Get(Box::new(expr), self.tokens_list[self.curr_index].clone());
+                self.curr_index += 1;
+                continue;
+            }
+
             if !self.check(TokenKind::LEFT_PAREN) {
                 break;
             }
@@ -484,16 +670,16 @@ impl Parser {
 
             if self.check(TokenKind::RIGHT_PAREN) {
                 if args_list.len() > 255 {
-                    return self.err_from(start_error, "Too many arguments: function calls can have at most 255 arguments.");
+                    return self.err_from(start_error, "D0208", "Too many arguments: function calls can have at most 255 arguments.");
                 }
                 expr = Expression::Call(
                     Box::new(expr),
-                    (&self.tokens_list[self.curr_index]).clone(),
+                    self.tokens_list[self.curr_index].clone(),
                     Box::new(args_list),
                 );
                 self.curr_index += 1;
             } else {
-                return self.err_from(start_error, "Expected ')' after argument list.");
+                return self.err_from(start_error, "D0202", "Expected ')' after argument list.");
             }
         }
 
@@ -510,10 +696,10 @@ impl Parser {
             let right_expr = self.expression()?;
 
             if !self.check(TokenKind::RIGHT_SQUARE) {
-                return self.err_from(start_error, "Expected ']' to close index expression.");
+                return self.err_from(start_error, "D0202", "Expected ']' to close index expression.");
             }
 
-            let right_brace_store = (&self.tokens_list[self.curr_index]).clone();
+            let right_brace_store = self.tokens_list[self.curr_index].clone();
             self.curr_index += 1;
 
             return Ok(Expression::Index(Box::new(left_expr), right_brace_store, Box::new(right_expr)));
@@ -526,7 +712,7 @@ impl Parser {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
         if self.atEnd() {
-            return self.err_from(start_error, "Unexpected end of input.");
+            return self.err_from(start_error, "D0206", "Unexpected end of input.");
         }
 
         let literal: &Token = &self.tokens_list[self.curr_index];
@@ -548,13 +734,14 @@ impl Parser {
                 } else {
                     return self.err_from_owned(
                         start_error,
+                        "D0207",
                         format!("Invalid number literal near '{}'.", literal.lexeme),
                     );
                 }
             }
-            TokenKind::IDENTIFIER => return Ok(Expression::Variable(literal.clone())),
-            TokenKind::LAMBDA => return Ok(self.lambda()?),
-            TokenKind::LEFT_SQUARE => return Ok(self.list()?),
+            TokenKind::IDENTIFIER => return Ok(Expression::Variable(literal.clone(), None)),
+            TokenKind::LAMBDA => return self.lambda(),
+            TokenKind::LEFT_SQUARE => return self.list(),
             _ => { self.curr_index -= 1; }
         }
 
@@ -565,11 +752,12 @@ impl Parser {
                 return Ok(Expression::Grouping(Box::new(expression)));
             }
 
-            return self.err_from(start_error, "Expected ')' to close parenthesized expression.");
+            return self.err_from(start_error, "D0202", "Expected ')' to close parenthesized expression.");
         }
 
         self.err_from_owned(
             start_error,
+            "D0206",
             format!("Unexpected token '{}'.", &self.tokens_list[self.curr_index].lexeme),
         )
     }
@@ -578,7 +766,7 @@ impl Parser {
         let start_error = self.tokens_list[self.curr_index].id as usize;
 
         if !self.check(TokenKind::LEFT_PAREN) {
-            return self.err_from(start_error, "Expected '(' after 'lambda'.");
+            return self.err_from(start_error, "D0203", "Expected '(' after 'lambda'.");
         }
 
         self.curr_index += 1;
@@ -588,10 +776,10 @@ impl Parser {
         loop {
             if !self.check(TokenKind::RIGHT_PAREN) {
                 if !self.check(TokenKind::IDENTIFIER) {
-                    return self.err_from(start_error, "Expected an identifier as a parameter name in lambda expression.");
+                    return self.err_from(start_error, "D0204", "Expected an identifier as a parameter name in lambda expression.");
                 }
 
-                args_list.push((&self.tokens_list[self.curr_index]).clone());
+                args_list.push(self.tokens_list[self.curr_index].clone());
                 self.curr_index += 1;
 
                 if !self.check(TokenKind::COMMA) {
@@ -605,7 +793,7 @@ impl Parser {
         }
 
         if !self.check(TokenKind::RIGHT_PAREN) {
-            return self.err_from(start_error, "Expected ')' after lambda parameter list.");
+            return self.err_from(start_error, "D0202", "Expected ')' after lambda parameter list.");
         }
 
         self.curr_index += 1;
@@ -613,12 +801,12 @@ impl Parser {
         let statement = self.statement()?;
 
         if !matches!(statement, Statement::Block(_)) {
-            return self.err_from(start_error, "Expected a block '{ ... }' for lambda body.");
+            return self.err_from(start_error, "D0205", "Expected a block '{ ... }' for lambda body.");
         }
 
         let Statement::Block(statements) = statement else { unreachable!() };
 
-        Ok(Expression::Lambda(args_list, statements))
+        Ok(Expression::Lambda(args_list, Rc::from(*statements)))
     }
 
     fn list(&mut self) -> ParseResult<Expression> {
@@ -640,7 +828,7 @@ impl Parser {
         }
 
         if !self.check(TokenKind::RIGHT_SQUARE) {
-            return self.err_from(start_error, "Expected ']' to close list literal.");
+            return self.err_from(start_error, "D0202", "Expected ']' to close list literal.");
         }
 
         let right_brace_store = self.tokens_list[self.curr_index].clone();
@@ -651,7 +839,11 @@ impl Parser {
 
     fn check(&mut self, kind: TokenKind) -> bool {
         if self.atEnd() { return false; }
-        (&self.tokens_list[self.curr_index]).kind == kind
+        self.tokens_list[self.curr_index].kind == kind
+    }
+
+    fn check_word(&mut self, word: &str) -> bool {
+        self.check(TokenKind::IDENTIFIER) && self.tokens_list[self.curr_index].lexeme == word
     }
 
     fn match_token(&mut self, args: &[TokenKind]) -> bool {
@@ -667,30 +859,13 @@ impl Parser {
         false
     }
 
-    fn handle_error<T>(&self, msg: &str, start: usize, end: usize) -> ParseResult<T> {
+    fn handle_error<T>(&self, code: &str, msg: &str, start: usize, end: usize) -> ParseResult<T> {
         let idx = self.curr_index.min(self.tokens_list.len().saturating_sub(1));
         let line = self.tokens_list[idx].line;
         let file = self.tokens_list[idx].file.clone();
+        let source = self.tokens_list[idx].source;
 
-        if !self.repl {
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
-
-            let src = fs::read_to_string(&file)
-                .unwrap_or_else(|_| "<could not read source file>".to_string());
-
-            Report::build(ReportKind::Error, (&file, (line.saturating_sub(1)) as usize..3))
-                .with_message("Parser Error")
-                .with_label(
-                    Label::new((&file, start..end))
-                        .with_message(msg)
-                        .with_color(a),
-                )
-                .finish()
-                .print((&file, Source::from(&src)))
-                .unwrap();
-        }
-
-        Err(format!("Parser Error: {}", msg))
+        let span = Span { file, source, start, end, line: line.max(0) as usize };
+        Err(Diagnostic::new(Phase::Parse, msg).with_code(code).with_span(span))
     }
 }
@@ -0,0 +1,291 @@
+use crate::ast::{BreakResult, Value};
+use crate::func::Func;
+use crate::interpreter::Interpreter;
+use libloading::Library;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::path::Path;
+use std::rc::Rc;
+
+/// The plugin ABI this interpreter speaks. Bumped whenever the types below change, so a plugin
+/// built against another version is refused instead of misread.
+pub const ABI_VERSION: u32 = 1;
+
+/// `extern "C" fn() -> u32`: the ABI version the plugin was built for
+pub const VERSION_SYMBOL: &str = "dingle_plugin_abi_version";
+
+/// `extern "C" fn(*mut PluginRegistrar)`: defines the plugin's functions
+pub const REGISTER_SYMBOL: &str = "dingle_plugin_register";
+
+/// A value crossing the plugin boundary. `tag` says which fields are meaningful.
+///
+/// Arguments stay valid for the duration of the call. A string or list a plugin returns must stay
+/// valid until the plugin is called again, e.g. by keeping it in a thread-local buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginValue {
+    pub tag: u32,
+    /// Int, and Bool as 0 or 1
+    pub int: i64,
+    pub float: f64,
+    /// String: UTF-8 bytes; List: `len` PluginValues
+    pub ptr: *const c_void,
+    pub len: usize,
+}
+
+impl PluginValue {
+    pub const NONE: u32 = 0;
+    pub const BOOL: u32 = 1;
+    pub const INT: u32 = 2;
+    pub const FLOAT: u32 = 3;
+    pub const STRING: u32 = 4;
+    pub const LIST: u32 = 5;
+
+    pub fn none() -> Self {
+        PluginValue { tag: PluginValue::NONE, int: 0, float: 0.0, ptr: std::ptr::null(), len: 0 }
+    }
+
+    pub fn bool(b: bool) -> Self {
+        PluginValue { tag: PluginValue::BOOL, int: b as i64, ..PluginValue::none() }
+    }
+
+    pub fn int(n: i64) -> Self {
+        PluginValue { tag: PluginValue::INT, int: n, ..PluginValue::none() }
+    }
+
+    pub fn float(f: f64) -> Self {
+        PluginValue { tag: PluginValue::FLOAT, float: f, ..PluginValue::none() }
+    }
+
+    /// Borrows `s`: it must outlive every use of the value
+    pub fn string(s: &str) -> Self {
+        PluginValue { tag: PluginValue::STRING, ptr: s.as_ptr() as *const c_void, len: s.len(), ..PluginValue::none() }
+    }
+
+    /// Borrows `items`: they must outlive every use of the value
+    pub fn list(items: &[PluginValue]) -> Self {
+        PluginValue { tag: PluginValue::LIST, ptr: items.as_ptr() as *const c_void, len: items.len(), ..PluginValue::none() }
+    }
+
+    /// A number as a Float; Ints are accepted too, like the built-ins' `Float` parameters
+    pub fn as_float(&self) -> Option<f64> {
+        match self.tag {
+            PluginValue::INT => Some(self.int as f64),
+            PluginValue::FLOAT => Some(self.float),
+            _ => None,
+        }
+    }
+
+    /// # Safety
+    /// `ptr` and `len` must describe live UTF-8 bytes, as they do for every value the host passes
+    pub unsafe fn as_str<'a>(&self) -> Option<&'a str> {
+        if self.tag != PluginValue::STRING {
+            return None;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) };
+        std::str::from_utf8(bytes).ok()
+    }
+
+    /// # Safety
+    /// `ptr` and `len` must describe live PluginValues, as they do for every value the host passes
+    pub unsafe fn as_list<'a>(&self) -> Option<&'a [PluginValue]> {
+        if self.tag != PluginValue::LIST {
+            return None;
+        }
+        Some(unsafe { std::slice::from_raw_parts(self.ptr as *const PluginValue, self.len) })
+    }
+}
+
+/// A plugin function: reads `argc` arguments and writes its result to `out`. It returns 0 on
+/// success; anything else is a runtime error, with `out` holding the message as a String.
+pub type PluginFn = unsafe extern "C" fn(args: *const PluginValue, argc: usize, out: *mut PluginValue) -> i32;
+
+/// Passed to the plugin's register function, which calls `define` once per function
+#[repr(C)]
+pub struct PluginRegistrar {
+    pub context: *mut c_void,
+    /// Defines a built-in named by `name_len` UTF-8 bytes at `name`, taking `arity` arguments
+    pub define: unsafe extern "C" fn(context: *mut c_void, name: *const u8, name_len: usize, arity: u32, func: PluginFn),
+}
+
+unsafe extern "C" fn collect(context: *mut c_void, name: *const u8, name_len: usize, arity: u32, func: PluginFn) {
+    let defined = unsafe { &mut *(context as *mut Vec<(String, usize, PluginFn)>) };
+    let name = unsafe { std::slice::from_raw_parts(name, name_len) };
+    defined.push((String::from_utf8_lossy(name).into_owned(), arity as usize, func));
+}
+
+/// Opens the shared library at `path` and defines its functions as built-ins, visible to
+/// modules imported afterwards too. Returns how many it defined.
+pub fn load(interpreter: &Interpreter, path: &str) -> Result<usize, String> {
+    if !Path::new(path).is_file() {
+        return Err(format!("Plugin error: no such file '{}'.", path));
+    }
+
+    // SAFETY: opening a library runs its initializers. A plugin is trusted native code, like the interpreter.
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| format!("Plugin error: could not load '{}': {}.", path, e))?;
+
+    let missing = |symbol: &str| format!("Plugin error: '{}' has no '{}' function; is it a Dingle plugin?", path, symbol);
+
+    // SAFETY: the symbols' types are fixed by the plugin ABI, checked by version before anything else is called
+    let version = unsafe { library.get::<unsafe extern "C" fn() -> u32>(VERSION_SYMBOL.as_bytes()) }
+        .map_err(|_| missing(VERSION_SYMBOL))?;
+    let version = unsafe { version() };
+
+    if version != ABI_VERSION {
+        return Err(format!(
+            "Plugin error: '{}' was built for plugin ABI version {}, but this interpreter supports version {}.",
+            path, version, ABI_VERSION
+        ));
+    }
+
+    let register = unsafe { library.get::<unsafe extern "C" fn(*mut PluginRegistrar)>(REGISTER_SYMBOL.as_bytes()) }
+        .map_err(|_| missing(REGISTER_SYMBOL))?;
+
+    let mut defined: Vec<(String, usize, PluginFn)> = Vec::new();
+    let mut registrar = PluginRegistrar {
+        context: &mut defined as *mut Vec<(String, usize, PluginFn)> as *mut c_void,
+        define: collect,
+    };
+    unsafe { register(&mut registrar) };
+
+    // Functions keep the library loaded for as long as any of them is reachable
+    let library = Rc::new(library);
+    let count = defined.len();
+    for (name, arity, func) in defined {
+        let func: Rc<dyn Func> = Rc::new(PluginFunction { name: name.clone(), arity, func, _library: Rc::clone(&library) });
+        interpreter.natives.borrow_mut().push((name.clone(), Rc::clone(&func)));
+        interpreter.define_builtin(&name, func);
+    }
+
+    Ok(count)
+}
+
+/// A function defined by a plugin
+struct PluginFunction {
+    name: String,
+    arity: usize,
+    func: PluginFn,
+    _library: Rc<Library>,
+}
+
+impl PluginFunction {
+    /// Lays `value` out as a PluginValue; `lists` owns the arrays it points into until the call returns
+    fn marshal(&self, value: &Value, lists: &mut Vec<Vec<PluginValue>>) -> Result<PluginValue, String> {
+        Ok(match value {
+            Value::None => PluginValue::none(),
+            Value::Bool(b) => PluginValue::bool(*b),
+            Value::Int(n) => match i64::try_from(*n) {
+                Ok(n) => PluginValue::int(n),
+                Err(_) => return Err(format!("Value error: '{}' takes Ints that fit in 64 bits, but got {}.", self.name, n)),
+            },
+            Value::Float(f) => PluginValue::float(*f),
+            Value::String(s) => PluginValue::string(s),
+            Value::List(items) => {
+                let items = items
+                    .borrow()
+                    .iter()
+                    .map(|item| self.marshal(item, lists))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Moving the Vec into `lists` leaves its buffer where it is
+                let value = PluginValue::list(&items);
+                lists.push(items);
+                value
+            }
+            Value::Call(..) | Value::Module(..) | Value::Native(..) => {
+                return Err(format!("Type error: '{}' is a plugin function; it can't take a Function, Module or host object.", self.name))
+            }
+        })
+    }
+
+    fn unmarshal(&self, value: &PluginValue) -> Result<Value, String> {
+        let invalid = || format!("Plugin error: '{}' returned an invalid value.", self.name);
+
+        Ok(match value.tag {
+            PluginValue::NONE => Value::None,
+            PluginValue::BOOL => Value::Bool(value.int != 0),
+            PluginValue::INT => Value::Int(value.int as i128),
+            PluginValue::FLOAT => Value::Float(value.float),
+            PluginValue::STRING => Value::String(String::from(unsafe { value.as_str() }.ok_or_else(invalid)?)),
+            PluginValue::LIST => {
+                let items = unsafe { value.as_list() }.ok_or_else(invalid)?;
+                let items = items.iter().map(|item| self.unmarshal(item)).collect::<Result<Vec<_>, _>>()?;
+                Value::List(Rc::new(RefCell::new(items)))
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+impl Func for PluginFunction {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        self.name.clone()
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.arity)
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != self.arity {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: '{}' takes {} argument(s), but got {}.",
+                self.name,
+                self.arity,
+                input_args.len()
+            )));
+        }
+
+        let mut lists = Vec::new();
+        let args = input_args
+            .iter()
+            .map(|arg| self.marshal(arg, &mut lists))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(BreakResult::error)?;
+
+        let mut out = PluginValue::none();
+        // SAFETY: `args` and everything it points into outlive the call
+        let status = unsafe { (self.func)(args.as_ptr(), args.len(), &mut out) };
+
+        if status != 0 {
+            let message = match self.unmarshal(&out) {
+                Ok(Value::String(message)) => message,
+                _ => format!("Plugin error: '{}' failed.", self.name),
+            };
+            return Err(BreakResult::error(message));
+        }
+
+        self.unmarshal(&out).map_err(BreakResult::error)
+    }
+}
+
+pub struct LoadPlugin;
+
+impl Func for LoadPlugin {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("load_plugin")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'load_plugin' takes 1 argument (path), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(path) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        load(&interpreter, &path).map_err(|msg| BreakResult::error_code("D0412", msg))?;
+        Ok(Value::None)
+    }
+}
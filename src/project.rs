@@ -0,0 +1,303 @@
+use crate::diagnostic::{Diagnostic, Phase};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const MANIFEST: &str = "dingle.toml";
+pub const LOCKFILE: &str = "dingle.lock";
+
+/// `dingle.toml`: the package itself and its path dependencies, keyed by package name
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    package: Package,
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Package {
+    name: String,
+    #[serde(default = "default_version")]
+    version: String,
+    #[serde(default = "default_entry")]
+    entry: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct Dependency {
+    path: String,
+    // If given, the dependency's own manifest must declare exactly this version
+    version: Option<String>,
+}
+
+fn default_version() -> String {
+    String::from("0.1.0")
+}
+
+fn default_entry() -> String {
+    String::from("src/main.dingle")
+}
+
+/// One resolved package, as written to `dingle.lock`
+#[derive(Serialize, PartialEq)]
+struct Locked {
+    name: String,
+    version: String,
+    path: String,
+}
+
+#[derive(Serialize, PartialEq)]
+struct Lockfile {
+    package: Vec<Locked>,
+}
+
+/// A project ready to run: its entry file and every dependency's entry file, by package name
+pub struct Project {
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    pub packages: HashMap<String, PathBuf>,
+}
+
+/// Walks up from `start` to the nearest directory containing a `dingle.toml`
+pub fn find_manifest(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(MANIFEST))
+        .find(|candidate| candidate.is_file())
+}
+
+/// `path` relative to the working directory when possible, for messages
+fn relative(path: &Path) -> String {
+    match (env::current_dir(), fs::canonicalize(path)) {
+        (Ok(cwd), Ok(path)) => display(&path, &cwd),
+        _ => path.display().to_string(),
+    }
+}
+
+/// A project error about the file at `path`
+fn error(code: &str, path: &Path, message: String) -> Diagnostic {
+    Diagnostic::new(Phase::Project, message).with_code(code).with_file(relative(path))
+}
+
+fn read_manifest(path: &Path) -> Result<Manifest, Diagnostic> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| error("D0501", path, format!("could not read '{}': {}.", relative(path), e)))?;
+
+    let manifest: Manifest = toml::from_str(&text)
+        .map_err(|e| error("D0501", path, format!("invalid manifest '{}': {}", relative(path), e.message())))?;
+
+    if !is_package_name(&manifest.package.name) {
+        return Err(error("D0501", path, format!(
+            "invalid package name '{}' in '{}': use letters, digits, '_' and '-'.",
+            manifest.package.name, relative(path)
+        )));
+    }
+
+    Ok(manifest)
+}
+
+fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "std"
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Loads the manifest at `manifest_path`, resolves its dependency graph and refreshes the lockfile
+pub fn load(manifest_path: &Path) -> Result<Project, Diagnostic> {
+    let root = manifest_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let root = fs::canonicalize(&root)
+        .map_err(|e| error("D0501", manifest_path, format!("could not read '{}': {}.", root.display(), e)))?;
+    let manifest = read_manifest(manifest_path)?;
+
+    let entry = root.join(&manifest.package.entry);
+    if !entry.is_file() {
+        return Err(error("D0501", manifest_path, format!(
+            "entry point '{}' of package '{}' does not exist.",
+            manifest.package.entry, manifest.package.name
+        )));
+    }
+
+    // Walks every path dependency, transitively; each package name may only ever mean one directory.
+    // Pending entries are (dependent package, its directory, dependency name, dependency spec).
+    let mut resolved: BTreeMap<String, (PathBuf, Manifest)> = BTreeMap::new();
+    let mut pending: Vec<(String, PathBuf, String, Dependency)> = manifest
+        .dependencies
+        .iter()
+        .map(|(name, dep)| (manifest.package.name.clone(), root.clone(), name.clone(), dep.clone()))
+        .collect();
+
+    while let Some((from, base, name, dep)) = pending.pop() {
+        let (from, name) = (from.as_str(), name.as_str());
+        // The manifest that asked for this dependency
+        let from_manifest = base.join(MANIFEST);
+
+        let dir = fs::canonicalize(base.join(&dep.path)).map_err(|e| error("D0502", &from_manifest, format!(
+            "dependency '{}' of '{}' at '{}' could not be read: {}.",
+            name, from, dep.path, e
+        )))?;
+
+        if name == manifest.package.name {
+            return Err(error("D0502", &from_manifest, format!(
+                "dependency '{}' of '{}' has the same name as the project itself.",
+                name, from
+            )));
+        }
+
+        if let Some((existing, found)) = resolved.get(name) {
+            if *existing != dir {
+                return Err(error("D0502", &from_manifest, format!(
+                    "dependency conflict: '{}' refers to both '{}' and '{}'.",
+                    name, display(existing, &root), display(&dir, &root)
+                )));
+            }
+            check_version(&from_manifest, from, name, &dep, &found.package.version)?;
+            continue;
+        }
+
+        let found = read_manifest(&dir.join(MANIFEST))?;
+
+        if found.package.name != name {
+            return Err(error("D0502", &from_manifest, format!(
+                "dependency '{}' of '{}' points at '{}', whose package is named '{}'.",
+                name, from, dep.path, found.package.name
+            )));
+        }
+        check_version(&from_manifest, from, name, &dep, &found.package.version)?;
+
+        if !dir.join(&found.package.entry).is_file() {
+            return Err(error("D0502", &dir.join(MANIFEST), format!(
+                "entry point '{}' of package '{}' does not exist.",
+                found.package.entry, name
+            )));
+        }
+
+        for (child, child_dep) in found.dependencies.iter() {
+            pending.push((String::from(name), dir.clone(), child.clone(), child_dep.clone()));
+        }
+
+        resolved.insert(String::from(name), (dir, found));
+    }
+
+    let lock = Lockfile {
+        package: resolved
+            .iter()
+            .map(|(name, (dir, found))| Locked {
+                name: name.clone(),
+                version: found.package.version.clone(),
+                path: display(dir, &root),
+            })
+            .collect(),
+    };
+    write_lock(&root.join(LOCKFILE), &lock)?;
+
+    let packages = resolved
+        .into_iter()
+        .map(|(name, (dir, found))| (name, dir.join(found.package.entry)))
+        .collect();
+
+    Ok(Project { root, entry, packages })
+}
+
+fn check_version(from_manifest: &Path, from: &str, name: &str, dep: &Dependency, found: &str) -> Result<(), Diagnostic> {
+    match &dep.version {
+        Some(wanted) if wanted != found => Err(error("D0502", from_manifest, format!(
+            "version conflict: '{}' requires {} {}, but '{}' is version {}.",
+            from, name, wanted, dep.path, found
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Only rewrites the lockfile when its contents change, so `run` doesn't dirty the tree
+fn write_lock(path: &Path, lock: &Lockfile) -> Result<(), Diagnostic> {
+    let write_error = |e: &dyn std::fmt::Display| error("D0501", path, format!("could not write '{}': {}.", path.display(), e));
+    let body = toml::to_string(lock).map_err(|e| write_error(&e))?;
+    let text = format!("# Generated by dinglebob. Do not edit.\n\n{}", body);
+
+    if fs::read_to_string(path).ok().as_deref() == Some(text.as_str()) {
+        return Ok(());
+    }
+
+    fs::write(path, text).map_err(|e| write_error(&e))
+}
+
+/// `path` relative to `base` when possible (both canonical), for messages and the lockfile
+pub fn display(path: &Path, base: &Path) -> String {
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+
+    let shared = path_parts.iter().zip(base_parts.iter()).take_while(|(a, b)| a == b).count();
+    if shared == 0 {
+        return path.display().to_string();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in shared..base_parts.len() {
+        relative.push("..");
+    }
+    for part in &path_parts[shared..] {
+        relative.push(part);
+    }
+
+    if relative.as_os_str().is_empty() {
+        String::from(".")
+    } else {
+        // Forward slashes keep the lockfile the same on every platform
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// `dinglebob new <name>`: a manifest, an entry point and an empty tests directory
+pub fn create(name: &str) -> Result<PathBuf, Diagnostic> {
+    if !is_package_name(name) {
+        return Err(Diagnostic::new(Phase::Project, format!(
+            "invalid package name '{}': use letters, digits, '_' and '-'.",
+            name
+        )).with_code("D0503"));
+    }
+
+    let dir = PathBuf::from(name);
+    if dir.exists() {
+        return Err(error("D0503", &dir, format!("'{}' already exists.", dir.display())));
+    }
+
+    let write = |path: PathBuf, text: String| {
+        fs::write(&path, text).map_err(|e| error("D0503", &path, format!("could not write '{}': {}.", path.display(), e)))
+    };
+    let mkdir = |path: PathBuf| {
+        fs::create_dir_all(&path).map_err(|e| error("D0503", &path, format!("could not create '{}': {}.", path.display(), e)))
+    };
+
+    mkdir(dir.join("src"))?;
+    mkdir(dir.join("tests"))?;
+
+    write(
+        dir.join(MANIFEST),
+        format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentry = \"src/main.dingle\"\n\n[dependencies]\n", name),
+    )?;
+    write(dir.join("src").join("main.dingle"), format!("print \"Hello from {}!\";\n", name))?;
+    write(
+        dir.join("tests").join("main_test.dingle"),
+        String::from("# Run with: dinglebob tests/main_test.dingle\nif 1 + 1 != 2 {\n    print \"FAIL: 1 + 1\";\n    exit(1);\n}\nprint \"ok\";\n"),
+    )?;
+
+    Ok(dir)
+}
+
+/// The manifest for `dinglebob run`: the nearest `dingle.toml` above the working directory
+pub fn find_from_cwd() -> Result<PathBuf, Diagnostic> {
+    let cwd = env::current_dir().map_err(|e| Diagnostic::new(
+        Phase::Project,
+        format!("could not read the working directory: {}.", e),
+    ).with_code("D0501"))?;
+
+    find_manifest(&cwd).ok_or_else(|| Diagnostic::new(Phase::Project, format!(
+        "could not find '{}' in '{}' or any parent directory.",
+        MANIFEST, cwd.display()
+    )).with_code("D0501"))
+}
@@ -0,0 +1,278 @@
+use crate::interpreter::Interpreter;
+use crate::environment::Environment;
+use crate::ast::Value;
+use crate::ast::BreakResult;
+use crate::func::Func;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SplitMix64: tiny, fast, and gives the same sequence for a seed on every platform
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Unseeded runs start from the clock, like `timeit`
+    pub fn from_time() -> Self {
+        let since_the_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards");
+
+        Rng::new(since_the_epoch.as_nanos() as u64)
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform in [0, bound), without modulo bias
+    pub fn below(&mut self, bound: u128) -> u128 {
+        let mut x = self.next_u128();
+        let zone = u128::MAX - (u128::MAX % bound);
+        while x >= zone {
+            x = self.next_u128();
+        }
+        x % bound
+    }
+
+    fn next_u128(&mut self) -> u128 {
+        ((self.next_u64() as u128) << 64) | self.next_u64() as u128
+    }
+}
+
+/// Builds the `random` module value; its functions close over `closure` like every other built-in
+pub fn module(closure: &Rc<RefCell<Environment>>) -> Value {
+    let members = Rc::new(RefCell::new(Environment::new(None)));
+
+    {
+        let mut edittable_env = members.borrow_mut();
+
+        let mut define = |name: &str, func: Box<dyn Func>| {
+            edittable_env.define_default(
+                name,
+                Value::Call(Rc::from(func), Rc::clone(closure))
+            )
+        };
+
+        define("seed", Box::new(Seed {}));
+        define("int", Box::new(RandInt {}));
+        define("float", Box::new(RandFloat {}));
+        define("choice", Box::new(Choice {}));
+        define("shuffle", Box::new(Shuffle {}));
+        define("sample", Box::new(Sample {}));
+    }
+
+    Value::Module(String::from("random"), members)
+}
+
+fn arity_error(name: &str, expected: &str, got: usize) -> BreakResult {
+    BreakResult::error_code("D0405", format!(
+        "Arity error: 'random.{}' takes {}, but got {}.",
+        name, expected, got
+    ))
+}
+
+pub struct Seed;
+
+impl Func for Seed {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.seed")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("seed", "1 argument", input_args.len()));
+        }
+
+        let Value::Int(n) = self.expect(input_args[0].clone(), "Int")? else {unreachable!()};
+
+        let Ok(seed) = u64::try_from(n) else {
+            return Err(BreakResult::error(format!(
+                "Value error: 'random.seed' seed {} is out of range; it must be from 0 to {}.",
+                n, u64::MAX
+            )));
+        };
+
+        interpreter.rng.borrow_mut().seed(seed);
+        Ok(Value::None)
+    }
+}
+
+pub struct RandInt;
+
+impl Func for RandInt {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.int")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("int", "2 arguments (low, high)", input_args.len()));
+        }
+
+        let Value::Int(low) = self.expect(input_args[0].clone(), "Int")? else {unreachable!()};
+        let Value::Int(high) = self.expect(input_args[1].clone(), "Int")? else {unreachable!()};
+
+        if low > high {
+            return Err(BreakResult::error(format!(
+                "Value error: 'random.int' low bound {} is greater than high bound {}.",
+                low, high
+            )));
+        }
+
+        // Both ends are inclusive
+        let span = (high.wrapping_sub(low) as u128).wrapping_add(1);
+        let offset = if span == 0 {
+            interpreter.rng.borrow_mut().next_u128()
+        } else {
+            interpreter.rng.borrow_mut().below(span)
+        };
+
+        Ok(Value::Int(low.wrapping_add(offset as i128)))
+    }
+}
+
+pub struct RandFloat;
+
+impl Func for RandFloat {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.float")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if !input_args.is_empty() {
+            return Err(arity_error("float", "0 arguments", input_args.len()));
+        }
+
+        Ok(Value::Float(interpreter.rng.borrow_mut().next_f64()))
+    }
+}
+
+pub struct Choice;
+
+impl Func for Choice {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.choice")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("choice", "1 argument", input_args.len()));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let items = lst.borrow();
+
+        if items.is_empty() {
+            return Err(BreakResult::error("Value error: 'random.choice' from an empty list."));
+        }
+
+        let index = interpreter.rng.borrow_mut().below(items.len() as u128) as usize;
+        Ok(items[index].clone())
+    }
+}
+
+pub struct Shuffle;
+
+impl Func for Shuffle {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.shuffle")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 1 {
+            return Err(arity_error("shuffle", "1 argument", input_args.len()));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+
+        // Fisher-Yates, in place like `append`
+        {
+            let mut items = lst.borrow_mut();
+            let mut rng = interpreter.rng.borrow_mut();
+            for i in (1..items.len()).rev() {
+                let j = rng.below((i + 1) as u128) as usize;
+                items.swap(i, j);
+            }
+        }
+
+        Ok(Value::List(Rc::clone(&lst)))
+    }
+}
+
+pub struct Sample;
+
+impl Func for Sample {
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("random.sample")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != 2 {
+            return Err(arity_error("sample", "2 arguments (list, count)", input_args.len()));
+        }
+
+        let Value::List(lst) = self.expect(input_args[0].clone(), "List")? else {unreachable!()};
+        let Value::Int(k) = self.expect(input_args[1].clone(), "Int")? else {unreachable!()};
+
+        let mut pool = lst.borrow().clone();
+        if k < 0 || k > pool.len() as i128 {
+            return Err(BreakResult::error(format!(
+                "Value error: 'random.sample' count must be in [0, {}], got {}.",
+                pool.len(), k
+            )));
+        }
+
+        // Partial Fisher-Yates: the first k slots end up as a sample without replacement
+        let mut rng = interpreter.rng.borrow_mut();
+        for i in 0..k as usize {
+            let j = i + rng.below((pool.len() - i) as u128) as usize;
+            pool.swap(i, j);
+        }
+        pool.truncate(k as usize);
+
+        Ok(Value::List(Rc::new(RefCell::new(pool))))
+    }
+}
@@ -1,68 +1,76 @@
 use crate::{
-    ast::{Expression, Statement, Value},
+    ast::{Expression, Local, Statement, Value},
     token::Token,
 };
-use std::{
-    cell::RefCell,
-    collections::{HashMap, VecDeque},
-    rc::Rc,
-};
-
-use ariadne::{ColorGenerator, Label, Report, ReportKind, Source};
-use std::fs;
-
-type ResolveResult<T> = Result<T, String>;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use crate::diagnostic::{Diagnostic, Phase};
+
+
+type ResolveResult<T> = Result<T, Diagnostic>;
+
+/// One block (or parameter list) being resolved; its locals live in one runtime frame
+#[derive(Clone)]
+pub struct Scope {
+    // Names declared so far, and their slot in the frame
+    declared: HashMap<String, usize>,
+    // Every name the block declares, including ones not reached yet: a function defined in
+    // the block may call one declared after it, as long as it runs after that declaration
+    hoisted: HashMap<String, usize>,
+    // The parameter scope of a function or lambda, where a new call frame starts
+    function: bool,
+}
 
 pub struct Resolver {
-    pub locals: Rc<RefCell<HashMap<Token, i32>>>,
-    pub stack: VecDeque<HashMap<String, bool>>,
-    pub repl: bool,
+    pub stack: VecDeque<Scope>,
+    // Top-level names bound by an import, and the token that bound them
+    pub imported: HashMap<String, Token>,
 }
 
 impl Resolver {
     pub fn clone(&mut self) -> Self {
         Resolver {
-            locals: Rc::new(RefCell::new(self.locals.borrow().clone())),
             stack: self.stack.clone(),
-            repl: self.repl,
+            imported: self.imported.clone(),
         }
     }
 
-    pub fn new(repl: bool) -> Self {
+    pub fn new() -> Self {
         Resolver {
-            locals: Rc::new(RefCell::new(HashMap::new())),
             stack: VecDeque::new(),
-            repl,
+            imported: HashMap::new(),
         }
     }
 
-    pub fn resolve(&mut self, statements: Vec<Statement>) -> ResolveResult<()> {
+    /// Annotates every local variable reference in `statements` with its frame depth and slot
+    pub fn resolve(&mut self, statements: &mut [Statement]) -> ResolveResult<()> {
         for statement in statements {
             self.resolve_stmt(statement)?;
         }
         Ok(())
     }
 
-    fn resolve_stmt(&mut self, stmt: Statement) -> ResolveResult<()> {
+    fn resolve_stmt(&mut self, stmt: &mut Statement) -> ResolveResult<()> {
         match stmt {
             Statement::Expression(exp) => self.resolve_exp(exp),
-            Statement::If(exp, then_s, else_s) => self.resolve_if(exp, *then_s, *else_s),
-            Statement::Function(t, vt, vs) => self.resolve_function(t, vt, *vs),
+            Statement::If(exp, then_s, else_s) => self.resolve_if(exp, then_s, else_s),
+            Statement::Function(t, vt, vs) => self.resolve_function(t, vt, vs),
             Statement::Print(exp) => self.resolve_print(exp),
             Statement::Return(t, val) => self.resolve_return(t, val),
             Statement::Var(var, value) => self.resolve_var(var, value),
-            Statement::Block(statements) => self.resolve_block(*statements),
-            Statement::While(exp, s) => self.resolve_while(exp, *s),
+            Statement::Block(statements) => self.resolve_block(statements),
+            Statement::While(exp, s) => self.resolve_while(exp, s),
             Statement::Break(t) => self.resolve_break(t),
-            _ => unreachable!(),
+            Statement::Export(_, stmt) => self.resolve_stmt(stmt),
+            Statement::FromImport(path, names) => self.resolve_from_import(path, names),
         }
     }
 
     fn resolve_if(
         &mut self,
-        exp: Expression,
-        then_s: Statement,
-        else_s: Statement,
+        exp: &mut Expression,
+        then_s: &mut Statement,
+        else_s: &mut Statement,
     ) -> ResolveResult<()> {
         self.resolve_exp(exp)?;
         self.resolve_stmt(then_s)?;
@@ -72,54 +80,103 @@ impl Resolver {
 
     fn resolve_function(
         &mut self,
-        name: Token,
-        params: Vec<Token>,
-        body: Vec<Statement>,
+        name: &Token,
+        params: &[Token],
+        body: &mut Rc<[Statement]>,
     ) -> ResolveResult<()> {
-        if !self.stack.is_empty() {
-            self.stack[0].insert(name.lexeme, true);
+        // Declared before the body so it can call itself
+        self.declare(name)?;
+        self.resolve_callable(params, body)
+    }
 
-            self.begin_scope();
+    /// A function or lambda: a scope for the parameters, then one for the body
+    fn resolve_callable(&mut self, params: &[Token], body: &mut Rc<[Statement]>) -> ResolveResult<()> {
+        // Bodies are only shared with closures once the program runs, after resolution
+        let body = Rc::get_mut(body).expect("function body shared before it was resolved");
 
-            for args in params {
-                self.stack[0].insert(args.lexeme, true);
-            }
+        self.begin_scope(params.iter(), true);
 
-            self.resolve_block(body)?;
-            self.end_scope();
+        for param in params {
+            self.declare(param)?;
         }
+
+        self.resolve_block(body)?;
+        self.end_scope();
         Ok(())
     }
 
-    fn resolve_print(&mut self, exp: Expression) -> ResolveResult<()> {
+    fn resolve_print(&mut self, exp: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(exp)
     }
 
-    fn resolve_return(&mut self, _token: Token, val: Expression) -> ResolveResult<()> {
+    fn resolve_return(&mut self, _token: &Token, val: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(val)
     }
 
-    fn resolve_var(&mut self, var: Token, value: Expression) -> ResolveResult<()> {
+    fn resolve_var(&mut self, var: &Token, value: &mut Expression) -> ResolveResult<()> {
+        // `let m = import("...")` at the top level binds a module just like `from ... import`
+        if self.stack.is_empty()
+            && let Expression::Call(callee, _, _) = &*value
+                && let Expression::Variable(name, _) = callee.as_ref()
+                    && name.lexeme == "import" {
+                        self.bind_import(var)?;
+                    }
+
         self.resolve_exp(value)?;
+        self.declare(var)
+    }
 
-        if !self.stack.is_empty() {
-            if self.stack[0].contains_key(&var.lexeme) {
-                return self.handle_error(
-                    &format!(
-                        "Duplicate definition: '{}' is already defined in this scope.",
-                        &var.lexeme
-                    ),
-                    &var,
-                );
-            }
-            self.stack[0].insert(var.lexeme.clone(), true);
+    /// Gives a local the next slot of the innermost frame; top-level names stay globals
+    fn declare(&mut self, name: &Token) -> ResolveResult<()> {
+        let Some(scope) = self.stack.front_mut() else {
+            return Ok(());
+        };
+
+        if scope.declared.contains_key(&name.lexeme) {
+            return self.handle_error(
+                "D0301",
+                &format!(
+                    "Duplicate definition: '{}' is already defined in this scope.",
+                    &name.lexeme
+                ),
+                name,
+            );
+        }
+
+        let slot = scope.declared.len();
+        scope.declared.insert(name.lexeme.clone(), slot);
+        Ok(())
+    }
+
+    fn resolve_from_import(&mut self, _path: &Token, names: &[(Token, Token)]) -> ResolveResult<()> {
+        for (_name, alias) in names {
+            self.bind_import(alias)?;
+        }
+        Ok(())
+    }
+
+    fn bind_import(&mut self, name: &Token) -> ResolveResult<()> {
+        if let Some(previous) = self.imported.get(&name.lexeme) {
+            return self.handle_error(
+                "D0302",
+                &format!(
+                    "Import conflict: '{}' is already bound by an import on line {}.",
+                    name.lexeme, previous.line
+                ),
+                name,
+            );
         }
 
+        self.imported.insert(name.lexeme.clone(), name.clone());
         Ok(())
     }
 
-    fn resolve_block(&mut self, statements: Vec<Statement>) -> ResolveResult<()> {
-        self.begin_scope();
+    fn resolve_block(&mut self, statements: &mut [Statement]) -> ResolveResult<()> {
+        let names = statements.iter().filter_map(|stmt| match stmt {
+            Statement::Var(name, _) | Statement::Function(name, _, _) => Some(name),
+            _ => None,
+        });
+        self.begin_scope(names, false);
 
         for statement in statements {
             self.resolve_stmt(statement)?;
@@ -129,54 +186,55 @@ impl Resolver {
         Ok(())
     }
 
-    fn resolve_while(&mut self, exp: Expression, body: Statement) -> ResolveResult<()> {
+    fn resolve_while(&mut self, exp: &mut Expression, body: &mut Statement) -> ResolveResult<()> {
         self.resolve_exp(exp)?;
         self.resolve_stmt(body)?;
         Ok(())
     }
 
-    fn resolve_break(&mut self, _token: Token) -> ResolveResult<()> {
+    fn resolve_break(&mut self, _token: &Token) -> ResolveResult<()> {
         Ok(())
     }
 
-    fn resolve_exp(&mut self, exp: Expression) -> ResolveResult<()> {
+    fn resolve_exp(&mut self, exp: &mut Expression) -> ResolveResult<()> {
         match exp {
-            Expression::Assign(t, a, v) => self.resolve_assign(*t, a, *v),
-            Expression::Binary(l, o, r) => self.resolve_binary(*l, o, *r),
-            Expression::Unary(o, r) => self.resolve_unary(o, *r),
-            Expression::Call(callee, paren, args) => self.resolve_call(*callee, paren, *args),
-            Expression::Logical(l, o, r) => self.resolve_logical(*l, o, *r),
+            Expression::Assign(t, a, v) => self.resolve_assign(t, a, v),
+            Expression::Binary(l, o, r) => self.resolve_binary(l, o, r),
+            Expression::Unary(o, r) => self.resolve_unary(o, r),
+            Expression::Call(callee, paren, args) => self.resolve_call(callee, paren, args),
+            Expression::Logical(l, o, r) => self.resolve_logical(l, o, r),
             Expression::Literal(v) => self.resolve_literal(v),
-            Expression::Grouping(exp) => self.resolve_grouping(*exp),
-            Expression::Variable(t) => self.resolve_variable(t),
-            Expression::Lambda(t, stmt) => self.resolve_lambda(t, *stmt),
-            Expression::Index(l, t, i) => self.resolve_index(*l, t, *i),
-            Expression::List(content, t) => self.resolve_list(*content, t),
+            Expression::Grouping(exp) => self.resolve_grouping(exp),
+            Expression::Variable(t, local) => self.resolve_variable(t, local),
+            Expression::Lambda(t, stmt) => self.resolve_callable(t, stmt),
+            Expression::Index(l, t, i) => self.resolve_index(l, t, i),
+            Expression::List(content, t) => self.resolve_list(content, t),
+            Expression::Get(object, name) => self.resolve_get(object, name),
         }
     }
 
     fn resolve_assign(
         &mut self,
-        assignee: Expression,
-        _equal: Token,
-        value: Expression,
+        assignee: &mut Expression,
+        _equal: &Token,
+        value: &mut Expression,
     ) -> ResolveResult<()> {
         self.resolve_exp(assignee)?;
         self.resolve_exp(value)?;
         Ok(())
     }
 
-    fn resolve_binary(&mut self, left: Expression, _operator: Token, right: Expression) -> ResolveResult<()> {
+    fn resolve_binary(&mut self, left: &mut Expression, _operator: &Token, right: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(left)?;
         self.resolve_exp(right)?;
         Ok(())
     }
 
-    fn resolve_unary(&mut self, _operator: Token, right: Expression) -> ResolveResult<()> {
+    fn resolve_unary(&mut self, _operator: &Token, right: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(right)
     }
 
-    fn resolve_call(&mut self, callee: Expression, _paren: Token, args: Vec<Expression>) -> ResolveResult<()> {
+    fn resolve_call(&mut self, callee: &mut Expression, _paren: &Token, args: &mut [Expression]) -> ResolveResult<()> {
         self.resolve_exp(callee)?;
         for arg in args {
             self.resolve_exp(arg)?;
@@ -184,104 +242,76 @@ impl Resolver {
         Ok(())
     }
 
-    fn resolve_logical(&mut self, left: Expression, _operator: Token, right: Expression) -> ResolveResult<()> {
+    fn resolve_logical(&mut self, left: &mut Expression, _operator: &Token, right: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(left)?;
         self.resolve_exp(right)?;
         Ok(())
     }
 
-    fn resolve_literal(&mut self, _value: Value) -> ResolveResult<()> {
+    fn resolve_literal(&mut self, _value: &Value) -> ResolveResult<()> {
         Ok(())
     }
 
-    fn resolve_grouping(&mut self, exp: Expression) -> ResolveResult<()> {
+    fn resolve_grouping(&mut self, exp: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(exp)
     }
 
-    fn resolve_variable(&mut self, name: Token) -> ResolveResult<()> {
-        if !self.stack.is_empty() {
-            let mut env_count = self.stack.len();
-
-            for i in 0..self.stack.len() {
-                if self.stack[i].contains_key(&name.lexeme) {
-                    env_count = i;
-                    break;
-                }
-            }
-
-            self.locals.borrow_mut().insert(name, env_count as i32);
-        }
-
-        Ok(())
-    }
+    fn resolve_variable(&mut self, name: &Token, local: &mut Option<Local>) -> ResolveResult<()> {
+        // Names not found in any local scope are globals or built-ins, looked up by name at runtime
+        let mut crossed_function = false;
 
-    fn resolve_lambda(&mut self, t: Vec<Token>, stmts: Vec<Statement>) -> ResolveResult<()> {
-        if !self.stack.is_empty() {
-            self.begin_scope();
+        for (depth, scope) in self.stack.iter().enumerate() {
+            let slot = match scope.declared.get(&name.lexeme) {
+                Some(slot) => Some(slot),
+                None if crossed_function => scope.hoisted.get(&name.lexeme),
+                None => None,
+            };
 
-            for args in t {
-                self.stack[0].insert(args.lexeme, true);
+            if let Some(&slot) = slot {
+                *local = Some(Local { depth, slot });
+                break;
             }
 
-            self.resolve_block(stmts)?;
-            self.end_scope();
+            crossed_function |= scope.function;
         }
+
         Ok(())
     }
 
-    fn resolve_index(&mut self, l: Expression, _t: Token, i: Expression) -> ResolveResult<()> {
+    fn resolve_index(&mut self, l: &mut Expression, _t: &Token, i: &mut Expression) -> ResolveResult<()> {
         self.resolve_exp(l)?;
         self.resolve_exp(i)?;
         Ok(())
     }
 
-    fn resolve_list(&mut self, content: Vec<Expression>, _t: Token) -> ResolveResult<()> {
+    fn resolve_list(&mut self, content: &mut [Expression], _t: &Token) -> ResolveResult<()> {
         for item in content {
             self.resolve_exp(item)?;
         }
         Ok(())
     }
 
-    fn begin_scope(&mut self) {
-        self.stack.push_front(HashMap::new());
+    fn resolve_get(&mut self, object: &mut Expression, _name: &Token) -> ResolveResult<()> {
+        // Member names are looked up at runtime, only the object is a variable reference
+        self.resolve_exp(object)
     }
 
-    fn end_scope(&mut self) {
-        self.stack.pop_front();
-    }
+    /// `names` are the declarations the scope will hold, in order, which fixes their slots
+    fn begin_scope<'a>(&mut self, names: impl Iterator<Item = &'a Token>, function: bool) {
+        let mut hoisted = HashMap::new();
+        for name in names {
+            let next = hoisted.len();
+            hoisted.entry(name.lexeme.clone()).or_insert(next);
+        }
 
-    pub fn give_local(&self) -> Rc<RefCell<HashMap<Token, i32>>> {
-        Rc::clone(&self.locals)
+        self.stack.push_front(Scope { declared: HashMap::new(), hoisted, function });
     }
 
-    fn handle_error<T>(&self, msg: &str, token: &Token) -> ResolveResult<T> {
-        if !self.repl {
-            let line = token.line;
-            let file = token.file.clone();
-            let start = token.id as usize;
-            let end = token.id_end as usize;
-
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
-
-            let src = fs::read_to_string(&file)
-                .unwrap_or_else(|_| "<could not read source file>".to_string());
-
-            Report::build(ReportKind::Error, (&file, (line.saturating_sub(1)) as usize..3))
-                .with_message("Resolver Error")
-                .with_label(
-                    Label::new((&file, start..end))
-                        .with_message(msg)
-                        .with_color(a),
-                )
-                .finish()
-                .print((&file, Source::from(&src)))
-                .unwrap();
-        }
+    fn end_scope(&mut self) {
+        self.stack.pop_front();
+    }
 
-        Err(format!(
-            "Semantic Error at '{}': {}",
-            token.lexeme, msg
-        ))
+    fn handle_error<T>(&self, code: &str, msg: &str, token: &Token) -> ResolveResult<T> {
+        Err(Diagnostic::at(Phase::Resolve, msg, token).with_code(code))
     }
 }
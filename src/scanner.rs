@@ -1,11 +1,11 @@
 use crate::token::Token;
 use crate::token::TokenKind;
-use std::fs;
+use crate::diagnostic::{Diagnostic, Phase, Span};
+use crate::source::SourceId;
+use std::mem;
 use std::str::Chars;
-use std::thread::current;
-use ariadne::{Color, ColorGenerator, Fmt, Label, Report, ReportKind, Source};
 
-type ScanResult<T> = Result<T, String>;
+type ScanResult<T> = Result<T, Diagnostic>;
 
 struct Scanner<'a> {
     curr_input: Chars<'a>,
@@ -13,45 +13,28 @@ struct Scanner<'a> {
     line: i32,
     token_id: i32,
     file: String,
-    repl: bool
+    source: SourceId,
+    errors: Vec<Diagnostic>,
+    max_errors: usize,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(input: &'a str, file: String, repl: bool) -> Self {
+    pub fn new(input: &'a str, file: String, source: SourceId, max_errors: usize) -> Self {
         Scanner {
             curr_input: input.chars(),
             token_list: Vec::new(),
             line: 1,
             token_id: 0,
-            file: file,
-            repl: repl
+            file,
+            source,
+            errors: Vec::new(),
+            max_errors,
         }
     }
 
-    fn err(&self, msg: &str, start: usize, end:usize) -> String {
-
-        if !(self.repl){
-            let mut colors = ColorGenerator::new();
-
-            // Generate & choose some colours for each of our elements
-            let a = colors.next();
-            let b = colors.next();
-            let out = Color::Fixed(81);
-            let src = fs::read_to_string(&self.file)
-            .unwrap_or_else(|_| "<could not read source file>".to_string());
-
-            Report::build(ReportKind::Error, (&self.file, (self.line - 1) as usize ..3))
-            .with_message(format!("{}", "Scanner Error"))
-            .with_label(
-                Label::new((&self.file, start..end))
-                    .with_message(msg)
-                    .with_color(a),
-            ).finish()
-            .print((&self.file, Source::from(&src)))
-            .unwrap();
-        }
-
-        format!("Scanner Error: {}", msg)
+    fn err(&self, code: &str, msg: &str, start: usize, end:usize) -> Diagnostic {
+        let span = Span { file: self.file.clone(), source: self.source, start, end, line: self.line as usize };
+        Diagnostic::new(Phase::Scan, msg).with_code(code).with_span(span)
     }
 
     fn add_token(&mut self, kind: TokenKind, lexeme: String) {
@@ -65,7 +48,8 @@ impl<'a> Scanner<'a> {
             line: self.line,
             id: curr_id_copy,
             id_end: self.token_id, 
-            file: self.file.clone()
+            file: self.file.clone(),
+            source: self.source,
         });
     }
 
@@ -88,7 +72,7 @@ impl<'a> Scanner<'a> {
         self.add_token(TokenKind::NUMBER, lexeme);
     }
 
-    fn handle_strings(&mut self) -> ScanResult<()> {
+    fn handle_strings(&mut self) {
         let mut string_content = String::new();
         let mut met_end = false;
 
@@ -108,9 +92,12 @@ impl<'a> Scanner<'a> {
 
         if met_end {
             self.add_token(TokenKind::STRING, string_content);
-            Ok(())
+            // The quotes are part of the token's span, though not of its lexeme
+            self.token_id += 2;
+            self.token_list.last_mut().unwrap().id_end = self.token_id;
         } else {
-            Err(self.err("Unterminated string literal: expected a closing '\"'.", self.token_id as usize, (self.token_id as usize) + string_content.chars().count() + 1))
+            let error = self.err("D0003", "Unterminated string literal: expected a closing '\"'.", self.token_id as usize, (self.token_id as usize) + string_content.chars().count() + 1);
+            self.errors.push(error);
         }
     }
 
@@ -143,6 +130,8 @@ impl<'a> Scanner<'a> {
             "define" => TokenKind::DEFINE,
             "break" => TokenKind::BREAK,
             "lambda" => TokenKind::LAMBDA,
+            "from" => TokenKind::FROM,
+            "export" => TokenKind::EXPORT,
             _ => TokenKind::IDENTIFIER,
         };
 
@@ -150,8 +139,12 @@ impl<'a> Scanner<'a> {
     }
 
     fn handle_comment(&mut self) {
+        // The '#' itself
+        self.token_id += 1;
+
         while let Some(c) = self.peak() {
             self.curr_input.next();
+            self.token_id += 1;
             if c == '\n' {
                 self.line += 1;
                 break;
@@ -172,13 +165,14 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    pub fn convert(&mut self) -> ScanResult<()> {
+    /// Scans the whole input, skipping over what it can't scan; every error is kept for `scan` to return
+    pub fn convert(&mut self) {
         loop {
             let curr_char = match self.curr_input.next() {
-                Some(c) => c,
-                None => {
+                Some(c) if self.errors.len() < self.max_errors => c,
+                _ => {
                     self.add_token(TokenKind::EOF, String::new());
-                    return Ok(());
+                    return;
                 }
             };
 
@@ -205,16 +199,18 @@ impl<'a> Scanner<'a> {
                 '!' => self.handle_equal(TokenKind::BANG, TokenKind::BANG_EQUAL, '!'),
                 '>' => self.handle_equal(TokenKind::GREATER, TokenKind::GREATER_EQUAL, '>'),
                 '<' => self.handle_equal(TokenKind::LESS, TokenKind::LESS_EQUAL, '<'),
-                '"' => self.handle_strings()?,
+                '"' => self.handle_strings(),
                 '0'..='9' => self.handle_digits(curr_char),
                 _ => {
                     if curr_char.is_alphabetic() || curr_char == '_' {
                         self.handle_identifier(curr_char);
                     } else {
-                        return Err(self.err(&format!(
+                        let error = self.err("D0002", &format!(
                             "Unexpected character '{}' (not valid in this language).",
                             curr_char
-                        ), self.token_id as usize, (self.token_id + 1)as usize));
+                        ), self.token_id as usize, (self.token_id + 1)as usize);
+                        self.errors.push(error);
+                        self.token_id += 1;
                     }
                 }
             }
@@ -232,9 +228,14 @@ impl<'a> Scanner<'a> {
     }
 }
 
-pub fn scan(contents: &str, debug: bool, file: String, repl: bool) -> ScanResult<Vec<Token>> {
-    let mut scanner = Scanner::new(contents, file, repl);
-    scanner.convert()?;
+/// The tokens of `contents`, or every error found in it (at most `max_errors`), the first leading
+pub fn scan(contents: &str, debug: bool, file: String, source: SourceId, max_errors: usize) -> ScanResult<Vec<Token>> {
+    let mut scanner = Scanner::new(contents, file, source, max_errors);
+    scanner.convert();
+
+    if let Some(errors) = Diagnostic::collect(mem::take(&mut scanner.errors)) {
+        return Err(errors);
+    }
 
     if debug {
         scanner.debug();
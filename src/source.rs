@@ -0,0 +1,62 @@
+use ariadne::{Cache, Source};
+use std::fmt;
+use std::rc::Rc;
+
+/// Which text in a `SourceMap` a token or span comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SourceId(usize);
+
+/// Every source text a run has scanned: files, imported modules, REPL lines and `eval` strings.
+/// Reports are rendered from here, so they work for text that never was a file, and show the
+/// code that actually ran even if the file has changed on disk since.
+#[derive(Default)]
+pub struct SourceMap {
+    sources: Vec<(String, Source<Rc<str>>)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Registers `text` under the name reports show for it
+    pub fn add(&mut self, name: &str, text: &str) -> SourceId {
+        self.sources.push((String::from(name), Source::from(Rc::from(text))));
+        SourceId(self.sources.len() - 1)
+    }
+
+    /// The file name, or `<eval>` for code that wasn't read from a file
+    pub fn name(&self, id: SourceId) -> &str {
+        &self.sources[id.0].0
+    }
+
+    pub fn text(&self, id: SourceId) -> &str {
+        self.sources[id.0].1.text()
+    }
+
+    /// The 1-based line and column of a character offset into a source
+    pub fn position(&self, id: SourceId, offset: usize) -> (usize, usize) {
+        let (mut line, mut column) = (1, 1);
+        for c in self.text(id).chars().take(offset) {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        (line, column)
+    }
+}
+
+impl Cache<SourceId> for &SourceMap {
+    type Storage = Rc<str>;
+
+    fn fetch(&mut self, id: &SourceId) -> Result<&Source<Rc<str>>, impl fmt::Debug> {
+        self.sources.get(id.0).map(|(_, source)| source).ok_or("unknown source")
+    }
+
+    fn display<'a>(&self, id: &'a SourceId) -> Option<impl fmt::Display + 'a> {
+        self.sources.get(id.0).map(|(name, _)| name.clone())
+    }
+}
@@ -0,0 +1,9 @@
+/// Standard library modules compiled into the binary, imported as `std/<name>`
+const MODULES: &[(&str, &str)] = &[
+    ("list", include_str!("../std/list.dingle")),
+    ("string", include_str!("../std/string.dingle")),
+];
+
+pub fn source(name: &str) -> Option<&'static str> {
+    MODULES.iter().find(|(n, _)| *n == name).map(|(_, src)| *src)
+}
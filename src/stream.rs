@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
+
+/// Where `print`, prompts or error reports go. Shared, so replacing the writer inside redirects
+/// every interpreter, environment and module holding it.
+pub type Output = Rc<RefCell<Box<dyn Write>>>;
+
+/// Where `input` and `read_line` read from
+pub type Input = Rc<RefCell<Box<dyn BufRead>>>;
+
+/// The three standard streams of one program
+#[derive(Clone)]
+pub struct Streams {
+    pub stdout: Output,
+    pub stderr: Output,
+    pub stdin: Input,
+}
+
+impl Streams {
+    /// The process's own stdout, stderr and stdin
+    pub fn process() -> Self {
+        Streams {
+            stdout: Rc::new(RefCell::new(Box::new(io::stdout()))),
+            stderr: Rc::new(RefCell::new(Box::new(io::stderr()))),
+            stdin: Rc::new(RefCell::new(Box::new(ProcessStdin { buffer: Vec::new(), pos: 0 }))),
+        }
+    }
+}
+
+/// The process's stdin, locked only for each read, so the host can read from it between calls
+struct ProcessStdin {
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ProcessStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.buffer.len() {
+            let n = (&self.buffer[self.pos..]).read(buf)?;
+            self.pos += n;
+            return Ok(n);
+        }
+        io::stdin().read(buf)
+    }
+}
+
+impl BufRead for ProcessStdin {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buffer.len() {
+            self.buffer.resize(8192, 0);
+            let n = io::stdin().read(&mut self.buffer)?;
+            self.buffer.truncate(n);
+            self.pos = 0;
+        }
+        Ok(&self.buffer[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.buffer.len());
+    }
+
+    /// Whole lines come straight from stdin's own buffer, leaving the rest for the host
+    fn read_line(&mut self, line: &mut String) -> io::Result<usize> {
+        let mut bytes = Vec::new();
+        if self.pos < self.buffer.len() {
+            let rest = &self.buffer[self.pos..];
+            let end = rest.iter().position(|&b| b == b'\n').map_or(rest.len(), |i| i + 1);
+            bytes.extend_from_slice(&rest[..end]);
+            self.pos += end;
+        }
+
+        let mut n = bytes.len();
+        let text = String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        line.push_str(&text);
+        if !text.ends_with('\n') {
+            n += io::stdin().read_line(line)?;
+        }
+        Ok(n)
+    }
+}
+
+/// An in-memory output stream. Clones share the same bytes, so one clone can be handed to the
+/// engine and the other kept to read what was written.
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        OutputBuffer::default()
+    }
+
+    /// Everything written so far, with invalid UTF-8 replaced
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// Everything written so far, leaving the buffer empty
+    pub fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.0.borrow_mut());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
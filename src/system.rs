@@ -0,0 +1,146 @@
+use crate::interpreter::Interpreter;
+use crate::ast::Value;
+use crate::ast::BreakResult;
+use crate::func::Func;
+use std::cell::RefCell;
+use std::env;
+use std::rc::Rc;
+
+pub struct Args;
+
+impl Func for Args {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("args")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if !input_args.is_empty() {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'args' takes 0 arguments, but got {}.",
+                input_args.len()
+            )));
+        }
+
+        // A fresh list each call, so scripts can't mutate the shared copy
+        let args = interpreter.script_args.iter().map(|a| Value::String(a.clone())).collect();
+
+        Ok(Value::List(Rc::new(RefCell::new(args))))
+    }
+}
+
+pub struct Env;
+
+impl Func for Env {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("env")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'env' takes 1 argument (name), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(name) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+
+        if let Some(value) = interpreter.env_vars.borrow().get(&name) {
+            return Ok(Value::String(value.clone()));
+        }
+
+        // Unset (or not valid Unicode) reads as none
+        match env::var(&name) {
+            Ok(value) => Ok(Value::String(value)),
+            Err(_) => Ok(Value::None),
+        }
+    }
+}
+
+pub struct SetEnv;
+
+impl Func for SetEnv {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("set_env")
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() != 2 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'set_env' takes 2 arguments (name, value), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let Value::String(name) = self.expect(input_args[0].clone(), "String")? else {unreachable!()};
+        let Value::String(value) = self.expect(input_args[1].clone(), "String")? else {unreachable!()};
+
+        // Names and values the process environment couldn't hold either
+        if name.is_empty() || name.contains('=') || name.contains('\0') || value.contains('\0') {
+            return Err(BreakResult::error(format!(
+                "Value error: 'set_env' got an invalid variable name or value for {:?}.",
+                name
+            )));
+        }
+
+        interpreter.env_vars.borrow_mut().insert(name, value);
+
+        Ok(Value::None)
+    }
+}
+
+pub struct Exit;
+
+impl Func for Exit {
+
+    fn isDefault(&self) -> bool {
+        true
+    }
+
+    fn toString(&self) -> String {
+        return String::from("exit")
+    }
+
+    fn call(&self, _interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult>{
+        if input_args.len() > 1 {
+            return Err(BreakResult::error_code("D0405", format!(
+                "Arity error: 'exit' takes 0 or 1 argument (code), but got {}.",
+                input_args.len()
+            )));
+        }
+
+        let code = match input_args.first() {
+            Some(code) => {
+                let Value::Int(code) = self.expect(code.clone(), "Int")? else {unreachable!()};
+                code
+            }
+            None => 0,
+        };
+
+        // Statuses wrap at 256, so a larger code could exit with 0 and read as success
+        let Ok(code) = u8::try_from(code) else {
+            return Err(BreakResult::error(format!(
+                "Value error: 'exit' code {} is out of range; it must be from 0 to 255.",
+                code
+            )));
+        };
+
+        // Unwinds like `return` so the host decides how to stop, instead of killing the process here
+        Err(BreakResult::Exit(code as i32))
+    }
+}
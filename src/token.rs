@@ -1,3 +1,5 @@
+use crate::source::SourceId;
+
 #[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
 pub enum TokenKind {
     LEFT_PAREN, RIGHT_PAREN, LEFT_BRACE, RIGHT_BRACE, LEFT_SQUARE, RIGHT_SQUARE, 
@@ -10,6 +12,7 @@ pub enum TokenKind {
 
     AND, CLASS, ELSE, FALSE, FOR, IF, NONE, OR, BREAK, 
     PRINT, RETURN, SUPER, THIS, TRUE, LET, WHILE,
+    FROM, EXPORT,
 
     NUMBER, 
     IDENTIFIER,
@@ -28,5 +31,6 @@ pub struct Token {
     pub id: i32,  
     pub id_end: i32, 
     pub file: String, 
+    pub source: SourceId,
 }
 
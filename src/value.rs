@@ -0,0 +1,102 @@
+use crate::ast;
+use crate::host::HostObject;
+use crate::interpreter::Interpreter;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A Dingle value as the host sees it. Lists are copied in and out of the engine; functions and
+/// modules stay inside it as `Object`s. `Native` objects are shared, not copied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    Object(Object),
+    Native(Rc<dyn HostObject>),
+}
+
+/// A function or module from a script. The host can hand it back to the engine but not look inside.
+#[derive(Clone)]
+pub struct Object(ast::Value);
+
+impl Object {
+    /// The name `type()` gives it in Dingle
+    pub fn type_name(&self) -> &'static str {
+        match self.0 {
+            ast::Value::Module(..) => "Module",
+            _ => "Function",
+        }
+    }
+}
+
+impl PartialEq for Object {
+    /// The same function or module, not merely an equal-looking one
+    fn eq(&self, other: &Object) -> bool {
+        match (&self.0, &other.0) {
+            (ast::Value::Call(a, _), ast::Value::Call(b, _)) => Rc::ptr_eq(a, b),
+            (ast::Value::Module(_, a), ast::Value::Module(_, b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Interpreter::stringify(&self.0))
+    }
+}
+
+impl Value {
+    /// The name `type()` gives the value in Dingle
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::None => "None",
+            Value::Bool(_) => "Bool",
+            Value::Int(_) => "Int",
+            Value::Float(_) => "Float",
+            Value::String(_) => "String",
+            Value::List(_) => "List",
+            Value::Object(object) => object.type_name(),
+            Value::Native(object) => object.type_name(),
+        }
+    }
+
+    pub(crate) fn from_internal(value: &ast::Value) -> Self {
+        match value {
+            ast::Value::None => Value::None,
+            ast::Value::Bool(b) => Value::Bool(*b),
+            ast::Value::Int(n) => Value::Int(*n),
+            ast::Value::Float(f) => Value::Float(*f),
+            ast::Value::String(s) => Value::String(s.clone()),
+            ast::Value::List(items) => Value::List(items.borrow().iter().map(Value::from_internal).collect()),
+            ast::Value::Call(..) | ast::Value::Module(..) => Value::Object(Object(value.clone())),
+            ast::Value::Native(object) => Value::Native(Rc::clone(object)),
+        }
+    }
+
+    pub(crate) fn into_internal(self) -> ast::Value {
+        match self {
+            Value::None => ast::Value::None,
+            Value::Bool(b) => ast::Value::Bool(b),
+            Value::Int(n) => ast::Value::Int(n),
+            Value::Float(f) => ast::Value::Float(f),
+            Value::String(s) => ast::Value::String(s),
+            Value::List(items) => ast::Value::List(Rc::new(RefCell::new(
+                items.into_iter().map(Value::into_internal).collect(),
+            ))),
+            Value::Object(object) => object.0,
+            Value::Native(object) => ast::Value::Native(object),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// The way `print` shows the value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Interpreter::stringify(&self.clone().into_internal()))
+    }
+}
@@ -0,0 +1,307 @@
+use crate::ast::{BreakResult, Statement, Value};
+use crate::compiler::{self, Op, Proto};
+use crate::environment::Environment;
+use crate::func::Func;
+use crate::interpreter::Interpreter;
+#[cfg(feature = "jit")]
+use crate::jit;
+use crate::diagnostic::Diagnostic;
+use crate::token::TokenKind;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Runs a whole program on the VM, or on the tree-walker when it can't be compiled
+/// (or `--tree-walker` asked for it). Same outcome as `Interpreter::prime_interpret`.
+pub fn run_program(interpreter: &mut Interpreter, statements: &[Statement]) -> Result<Option<i32>, Diagnostic> {
+    if interpreter.tree_walker {
+        return interpreter.prime_interpret(statements);
+    }
+
+    let Ok(script) = compiler::compile(statements) else {
+        return interpreter.prime_interpret(statements);
+    };
+
+    let globals = Rc::clone(&interpreter.global_environment);
+    let script = Closure { proto: script, upvalues: Vec::new(), globals };
+    let result = Vm { host: interpreter.frame(&interpreter.global_environment) }.run(&script, Vec::new());
+    interpreter.finish(result)
+}
+
+/// A compiled function value: its code, the variables it captured and the globals it was defined in
+pub struct Closure {
+    proto: Rc<Proto>,
+    upvalues: Vec<Rc<RefCell<Value>>>,
+    globals: Rc<RefCell<Environment>>,
+}
+
+impl Func for Closure {
+    fn isDefault(&self) -> bool {
+        false
+    }
+
+    fn toString(&self) -> String {
+        match &self.proto.name {
+            Some(name) => name.lexeme.clone(),
+            None => {
+                let params: Vec<&str> = self.proto.params.iter().map(|p| p.lexeme.as_str()).collect();
+                format!("Lambda({})", params.join(","))
+            }
+        }
+    }
+
+    fn arity(&self) -> Option<usize> {
+        Some(self.proto.params.len())
+    }
+
+    fn compiled(&self) -> Option<&Closure> {
+        Some(self)
+    }
+
+    fn call(&self, interpreter: Interpreter, input_args: Vec<Value>) -> Result<Value, BreakResult> {
+        if input_args.len() != self.proto.params.len() {
+            return Err(BreakResult::error_code("D0405", match &self.proto.name {
+                Some(name) => format!(
+                    "Arity error: function '{}' expects {} argument(s), but got {}.",
+                    name.lexeme,
+                    self.proto.params.len(),
+                    input_args.len()
+                ),
+                None => format!(
+                    "Arity error: lambda expects {} argument(s), but got {}.",
+                    self.proto.params.len(),
+                    input_args.len()
+                ),
+            }));
+        }
+
+        Vm { host: interpreter }.enter(self, input_args)
+    }
+}
+
+/// A local variable: a plain value, or a cell once a closure has captured it
+enum Slot {
+    Val(Value),
+    Cell(Rc<RefCell<Value>>),
+}
+
+/// Executes bytecode. Errors are reported through `host`, so they read exactly like the tree-walker's.
+struct Vm {
+    host: Interpreter,
+}
+
+impl Vm {
+    /// Calls a function: natively if the JIT has compiled it for these arguments, else on the VM
+    fn enter(&mut self, closure: &Closure, args: Vec<Value>) -> Result<Value, BreakResult> {
+        #[cfg(feature = "jit")]
+        if let Some(body) = &closure.proto.body {
+            let callee = jit::Callee {
+                name: closure.proto.name.as_ref(),
+                params: &closure.proto.params,
+                body,
+                env: &closure.globals,
+                this: closure as *const Closure as *const (),
+            };
+            if let Some(result) = self.host.jit.borrow_mut().call(callee, &args) {
+                return Ok(result);
+            }
+        }
+
+        self.run(closure, args)
+    }
+
+    /// Runs one call of `closure` to completion; each call gets its own locals and operand stack
+    fn run(&mut self, closure: &Closure, args: Vec<Value>) -> Result<Value, BreakResult> {
+        let proto = &*closure.proto;
+        let mut locals: Vec<Slot> = args.into_iter().map(Slot::Val).collect();
+        locals.resize_with(proto.slots.max(locals.len()), || Slot::Val(Value::None));
+
+        let mut stack: Vec<Value> = Vec::new();
+        let mut ip = 0;
+
+        loop {
+            let op = proto.code[ip];
+            ip += 1;
+
+            match op {
+                Op::Constant(c) => stack.push(proto.constants[c as usize].clone()),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::GetLocal(s) => stack.push(match &locals[s as usize] {
+                    Slot::Val(v) => v.clone(),
+                    Slot::Cell(cell) => cell.borrow().clone(),
+                }),
+                Op::SetLocal(s) => {
+                    let value = stack.pop().unwrap();
+                    match &mut locals[s as usize] {
+                        Slot::Val(v) => *v = value,
+                        Slot::Cell(cell) => *cell.borrow_mut() = value,
+                    }
+                }
+                Op::DefineLocal(s) => locals[s as usize] = Slot::Val(stack.pop().unwrap()),
+                Op::ClearLocal(s) => locals[s as usize] = Slot::Val(Value::None),
+                Op::GetUpvalue(u) => stack.push(closure.upvalues[u as usize].borrow().clone()),
+                Op::SetUpvalue(u) => *closure.upvalues[u as usize].borrow_mut() = stack.pop().unwrap(),
+                Op::GetGlobal(t) => {
+                    let token = &proto.tokens[t as usize];
+                    let found = closure.globals.borrow().lookup(&token.lexeme);
+                    match found {
+                        Some(v) => stack.push(v),
+                        // Not bound anywhere: let the environment report it
+                        None => stack.push(closure.globals.borrow().get(token.clone())?),
+                    }
+                }
+                Op::SetGlobal(t) => {
+                    let token = &proto.tokens[t as usize];
+                    let value = stack.pop().unwrap();
+                    let mut globals = closure.globals.borrow_mut();
+                    if !globals.assign_existing(&token.lexeme, value.clone()) {
+                        // Reported, but not fatal, as in `evaluate_assign`
+                        if let Err(e) = globals.assign(token.clone(), value) {
+                            self.host.report(e);
+                        }
+                    }
+                }
+                Op::DefineGlobal(t) => {
+                    let value = stack.pop().unwrap();
+                    closure.globals.borrow_mut().define(proto.tokens[t as usize].clone(), value)?;
+                }
+                Op::Binary(kind, t) => {
+                    let r = stack.pop().unwrap();
+                    let l = stack.pop().unwrap();
+                    stack.push(match (kind, &l, &r) {
+                        (TokenKind::PLUS, Value::Int(m), Value::Int(n)) => Value::Int(m + n),
+                        (TokenKind::MINUS, Value::Int(m), Value::Int(n)) => Value::Int(m - n),
+                        (TokenKind::STAR, Value::Int(m), Value::Int(n)) => Value::Int(m * n),
+                        (TokenKind::LESS, Value::Int(m), Value::Int(n)) => Value::Bool(m < n),
+                        (TokenKind::LESS_EQUAL, Value::Int(m), Value::Int(n)) => Value::Bool(m <= n),
+                        (TokenKind::GREATER, Value::Int(m), Value::Int(n)) => Value::Bool(m > n),
+                        (TokenKind::GREATER_EQUAL, Value::Int(m), Value::Int(n)) => Value::Bool(m >= n),
+                        _ => self.host.binary_op(l, &proto.tokens[t as usize], r)?,
+                    });
+                }
+                Op::Unary(t) => {
+                    let r = stack.pop().unwrap();
+                    stack.push(self.host.unary_op(&proto.tokens[t as usize], r)?);
+                }
+                Op::Jump(target) => ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    let condition = stack.pop().unwrap();
+                    if !self.host.to_bool(&condition) {
+                        ip = target as usize;
+                    }
+                }
+                Op::JumpIfFalseKeep(target) => {
+                    if !self.host.to_bool(stack.last().unwrap()) {
+                        ip = target as usize;
+                    } else {
+                        stack.pop();
+                    }
+                }
+                Op::JumpIfTrueKeep(target) => {
+                    if self.host.to_bool(stack.last().unwrap()) {
+                        ip = target as usize;
+                    } else {
+                        stack.pop();
+                    }
+                }
+                Op::Call(argc, t, exit) => {
+                    let args = stack.split_off(stack.len() - argc as usize);
+                    let callee = stack.pop().unwrap();
+
+                    match self.call(callee, args, &proto.tokens[t as usize]) {
+                        Ok(v) => stack.push(v),
+                        // A `break` inside the callee ends the caller's loop, as in the tree-walker
+                        Err(BreakResult::Break(_)) if exit.is_some() => {
+                            stack.clear();
+                            ip = exit.unwrap() as usize;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Op::Closure(p) => {
+                    let child = Rc::clone(&proto.protos[p as usize]);
+                    let upvalues = child
+                        .captures
+                        .iter()
+                        .map(|capture| {
+                            if capture.local {
+                                capture_slot(&mut locals[capture.index as usize])
+                            } else {
+                                Rc::clone(&closure.upvalues[capture.index as usize])
+                            }
+                        })
+                        .collect();
+
+                    let globals = Rc::clone(&closure.globals);
+                    let value = Closure { proto: child, upvalues, globals: Rc::clone(&globals) };
+                    stack.push(Value::Call(Rc::new(value), globals));
+                }
+                Op::Return => return Ok(stack.pop().unwrap()),
+                Op::ReturnOut(t) => {
+                    let value = stack.pop().unwrap();
+                    return Err(BreakResult::Return(proto.tokens[t as usize].clone(), value));
+                }
+                Op::BreakOut(t) => return Err(BreakResult::Break(proto.tokens[t as usize].clone())),
+                Op::Print => self.host.print(&stack.pop().unwrap())?,
+                Op::List(n) => {
+                    let items = stack.split_off(stack.len() - n as usize);
+                    stack.push(Value::List(Rc::new(RefCell::new(items))));
+                }
+                Op::Index(t) => {
+                    let i = stack.pop().unwrap();
+                    let l = stack.pop().unwrap();
+                    stack.push(self.host.index_value(l, &proto.tokens[t as usize], i)?);
+                }
+                Op::SetIndex(t) => {
+                    let i = stack.pop().unwrap();
+                    let l = stack.pop().unwrap();
+                    let value = stack.pop().unwrap();
+                    stack.push(self.host.assign_index(l, &proto.tokens[t as usize], i, value)?);
+                }
+                Op::SetMember(t) => {
+                    let object = stack.pop().unwrap();
+                    let value = stack.pop().unwrap();
+                    stack.push(self.host.set_member(object, &proto.tokens[t as usize], value)?);
+                }
+                Op::Get(t) => {
+                    let object = stack.pop().unwrap();
+                    stack.push(self.host.get_member(object, &proto.tokens[t as usize])?);
+                }
+                Op::FromImport(i) => {
+                    let (path, names) = &proto.imports[i as usize];
+                    self.host.frame(&closure.globals).execute_from_import(path, names)?;
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, callee: Value, args: Vec<Value>, paren: &crate::token::Token) -> Result<Value, BreakResult> {
+        let Value::Call(call, env) = callee else {
+            return Err(self.host.not_callable(&callee, paren));
+        };
+
+        self.host.enter_call(&call, paren);
+        let result = match call.compiled() {
+            // Compiled code calls compiled code without leaving the VM
+            Some(closure) if closure.proto.params.len() == args.len() => self.enter(closure, args),
+            _ => {
+                let mut frame = self.host.frame(&env);
+                frame.call_site = Some(paren.clone());
+                call.call(frame, args)
+            }
+        };
+        self.host.leave_call(result)
+    }
+}
+
+/// Moves a local into a shared cell the first time a closure captures it
+fn capture_slot(slot: &mut Slot) -> Rc<RefCell<Value>> {
+    if let Slot::Val(v) = slot {
+        *slot = Slot::Cell(Rc::new(RefCell::new(std::mem::replace(v, Value::None))));
+    }
+    match slot {
+        Slot::Cell(cell) => Rc::clone(cell),
+        Slot::Val(_) => unreachable!(),
+    }
+}